@@ -0,0 +1,124 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{IndexReader, IndexWriter, ValueHandle};
+use std::{future::Future, pin::Pin, sync::Arc};
+use tokio::runtime::Handle;
+
+/// Boxed future returned by the async index traits
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Trait that allows reading from an external index asynchronously
+///
+/// See [`IndexReader`].
+#[allow(clippy::module_name_repetitions)]
+pub trait AsyncReader: Send + Sync {
+    /// Returns a value handle for a given key.
+    ///
+    /// See [`IndexReader::get`].
+    fn get<'a>(&'a self, key: &'a [u8]) -> BoxFuture<'a, std::io::Result<Option<ValueHandle>>>;
+
+    /// Returns the value handles for the given keys, in the same order.
+    ///
+    /// See [`IndexReader::get_many`].
+    fn get_many<'a>(
+        &'a self,
+        keys: &'a [&'a [u8]],
+    ) -> BoxFuture<'a, std::io::Result<Vec<Option<ValueHandle>>>> {
+        Box::pin(async move {
+            let mut vhandles = Vec::with_capacity(keys.len());
+
+            for key in keys {
+                vhandles.push(self.get(key).await?);
+            }
+
+            Ok(vhandles)
+        })
+    }
+}
+
+/// Trait that allows writing into an external index asynchronously
+///
+/// See [`IndexWriter`].
+#[allow(clippy::module_name_repetitions)]
+pub trait AsyncWriter: Send {
+    /// Inserts a value handle into the index write batch.
+    ///
+    /// See [`IndexWriter::insert_indirect`].
+    fn insert_indirect<'a>(
+        &'a mut self,
+        key: &'a [u8],
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> BoxFuture<'a, std::io::Result<()>>;
+
+    /// Inserts a value handle into the index write batch, but only if the key
+    /// still points to `expected`.
+    ///
+    /// See [`IndexWriter::insert_indirect_if`].
+    fn insert_indirect_if<'a>(
+        &'a mut self,
+        key: &'a [u8],
+        expected: &'a ValueHandle,
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> BoxFuture<'a, std::io::Result<()>> {
+        let _ = expected;
+        self.insert_indirect(key, vhandle, size)
+    }
+
+    /// Finishes the write batch.
+    ///
+    /// See [`IndexWriter::finish`].
+    fn finish(&mut self) -> BoxFuture<'_, std::io::Result<()>>;
+}
+
+/// Drives an async index reader from a blocking thread
+pub struct BlockingReader<R: AsyncReader> {
+    pub(crate) inner: Arc<R>,
+    pub(crate) runtime: Handle,
+}
+
+impl<R: AsyncReader> IndexReader for BlockingReader<R> {
+    fn get(&self, key: &[u8]) -> std::io::Result<Option<ValueHandle>> {
+        self.runtime.block_on(self.inner.get(key))
+    }
+
+    fn get_many(&self, keys: &[&[u8]]) -> std::io::Result<Vec<Option<ValueHandle>>> {
+        self.runtime.block_on(self.inner.get_many(keys))
+    }
+}
+
+/// Drives an async index writer from a blocking thread
+pub struct BlockingWriter<W: AsyncWriter> {
+    pub(crate) inner: W,
+    pub(crate) runtime: Handle,
+}
+
+impl<W: AsyncWriter> IndexWriter for BlockingWriter<W> {
+    fn insert_indirect(
+        &mut self,
+        key: &[u8],
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> std::io::Result<()> {
+        self.runtime
+            .block_on(self.inner.insert_indirect(key, vhandle, size))
+    }
+
+    fn insert_indirect_if(
+        &mut self,
+        key: &[u8],
+        expected: &ValueHandle,
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> std::io::Result<()> {
+        self.runtime
+            .block_on(self.inner.insert_indirect_if(key, expected, vhandle, size))
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.runtime.block_on(self.inner.finish())
+    }
+}
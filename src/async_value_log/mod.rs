@@ -0,0 +1,236 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+pub mod index;
+
+use crate::{
+    gc::report::GcReport, id::SegmentId, value::UserValue, Compressor, Config, GcStrategy,
+    SegmentWriter, ValueHandle, ValueLog,
+};
+use index::{AsyncReader, AsyncWriter, BlockingReader, BlockingWriter};
+use std::{path::PathBuf, sync::Arc};
+use tokio::runtime::Handle;
+
+/// Async facade over a [`ValueLog`], for use with [`tokio`](https://tokio.rs)
+///
+/// Every operation that may block on disk I/O is run on a blocking thread,
+/// so executor threads are never blocked.
+///
+/// By default, the blocking thread pool of the current runtime is used
+/// (see [`tokio::task::spawn_blocking`]). To limit or isolate the threads that perform I/O,
+/// a dedicated runtime can be set using [`AsyncValueLog::with_blocking_pool`].
+#[derive(Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct AsyncValueLog<C: Compressor + Clone> {
+    inner: ValueLog<C>,
+    blocking_pool: Option<Handle>,
+}
+
+impl<C: Compressor + Clone> From<ValueLog<C>> for AsyncValueLog<C> {
+    fn from(value: ValueLog<C>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<C: Compressor + Clone> AsyncValueLog<C> {
+    /// Wraps a value log.
+    #[must_use]
+    pub fn new(value_log: ValueLog<C>) -> Self {
+        Self {
+            inner: value_log,
+            blocking_pool: None,
+        }
+    }
+
+    /// Sets the runtime whose blocking thread pool is used to perform I/O.
+    ///
+    /// Default = the runtime the operation is called from
+    #[must_use]
+    pub fn with_blocking_pool(mut self, runtime: Handle) -> Self {
+        self.blocking_pool = Some(runtime);
+        self
+    }
+
+    /// Returns the underlying value log.
+    #[must_use]
+    pub fn inner(&self) -> &ValueLog<C> {
+        &self.inner
+    }
+}
+
+impl<C: Compressor + Clone + Send + Sync + 'static> AsyncValueLog<C> {
+    /// Creates or recovers a value log in the given directory.
+    ///
+    /// See [`ValueLog::open`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub async fn open<P: Into<PathBuf>>(path: P, config: Config<C>) -> crate::Result<Self> {
+        let path = path.into();
+
+        let value_log = spawn_blocking(None, move || ValueLog::open(path, config)).await?;
+
+        Ok(Self::new(value_log))
+    }
+
+    async fn run<T, F>(&self, f: F) -> crate::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(ValueLog<C>) -> crate::Result<T> + Send + 'static,
+    {
+        let value_log = self.inner.clone();
+        spawn_blocking(self.blocking_pool.as_ref(), move || f(value_log)).await
+    }
+
+    /// Resolves a value handle.
+    ///
+    /// See [`ValueLog::get`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the blob's checksum
+    /// does not match (see [`Config::checksum_verification`]).
+    pub async fn get(&self, vhandle: &ValueHandle) -> crate::Result<Option<UserValue>> {
+        let vhandle = vhandle.clone();
+        self.run(move |value_log| value_log.get(&vhandle)).await
+    }
+
+    /// Resolves multiple value handles.
+    ///
+    /// See [`ValueLog::multi_get`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the blocking task fails.
+    /// Each result may fail individually, see [`AsyncValueLog::get`].
+    pub async fn multi_get(
+        &self,
+        vhandles: Vec<ValueHandle>,
+    ) -> crate::Result<Vec<crate::Result<Option<UserValue>>>> {
+        self.run(move |value_log| Ok(value_log.multi_get(&vhandles)))
+            .await
+    }
+
+    /// Initializes a new segment writer.
+    ///
+    /// See [`ValueLog::get_writer`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub async fn get_writer(&self) -> crate::Result<SegmentWriter<C>> {
+        self.run(|value_log| value_log.get_writer()).await
+    }
+
+    /// Registers a [`SegmentWriter`].
+    ///
+    /// See [`ValueLog::register_writer`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub async fn register_writer(&self, writer: SegmentWriter<C>) -> crate::Result<()> {
+        self.run(move |value_log| value_log.register_writer(writer))
+            .await
+    }
+
+    /// Scans the given index and collects GC statistics.
+    ///
+    /// See [`ValueLog::scan_for_stats`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub async fn scan_for_stats(
+        &self,
+        iter: impl Iterator<Item = std::io::Result<(ValueHandle, u32)>> + Send + 'static,
+    ) -> crate::Result<GcReport> {
+        self.run(move |value_log| value_log.scan_for_stats(iter))
+            .await
+    }
+
+    /// Applies a GC strategy.
+    ///
+    /// See [`ValueLog::apply_gc_strategy`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub async fn apply_gc_strategy<R: AsyncReader + 'static, W: AsyncWriter + 'static>(
+        &self,
+        strategy: &(impl GcStrategy<C> + Sync),
+        index_reader: Arc<R>,
+        index_writer: W,
+    ) -> crate::Result<u64> {
+        // NOTE: Picking only looks at in-memory statistics
+        let segment_ids = strategy.pick(&self.inner);
+        self.rollover(segment_ids, index_reader, index_writer).await
+    }
+
+    /// Rewrites some segments into new segment(s).
+    ///
+    /// The index is accessed from the blocking thread by driving the index futures
+    /// on the runtime this is called from.
+    /// With a `current_thread` runtime, the index futures cannot drive I/O resources
+    /// of that runtime, so a multi-threaded runtime should be used.
+    ///
+    /// See [`ValueLog::rollover`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub async fn rollover<R: AsyncReader + 'static, W: AsyncWriter + 'static>(
+        &self,
+        ids: Vec<SegmentId>,
+        index_reader: Arc<R>,
+        index_writer: W,
+    ) -> crate::Result<u64> {
+        let runtime = Handle::current();
+
+        self.run(move |value_log| {
+            let index_reader = BlockingReader {
+                inner: index_reader,
+                runtime: runtime.clone(),
+            };
+
+            let index_writer = BlockingWriter {
+                inner: index_writer,
+                runtime,
+            };
+
+            value_log.rollover(&ids, &index_reader, index_writer)
+        })
+        .await
+    }
+
+    /// Drops stale segments, and segments in which all blobs have expired.
+    ///
+    /// See [`ValueLog::drop_stale_segments`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub async fn drop_stale_segments(&self) -> crate::Result<u64> {
+        self.run(|value_log| value_log.drop_stale_segments()).await
+    }
+}
+
+/// Runs a blocking operation on the given runtime's (or the current runtime's) blocking thread pool.
+async fn spawn_blocking<T, F>(runtime: Option<&Handle>, f: F) -> crate::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> crate::Result<T> + Send + 'static,
+{
+    let task = match runtime {
+        Some(runtime) => runtime.spawn_blocking(f),
+        None => tokio::task::spawn_blocking(f),
+    };
+
+    match task.await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(crate::Error::Io(std::io::Error::other(e))),
+    }
+}
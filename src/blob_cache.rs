@@ -2,10 +2,16 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{value::UserValue, value_log::ValueLogId, ValueHandle};
+use crate::{
+    value::{UserKey, UserValue},
+    value_log::ValueLogId,
+    ValueHandle,
+};
 use quick_cache::{sync::Cache, Equivalent, Weighter};
 
-type Item = UserValue;
+// NOTE: The key is cached alongside the value, so key-checked
+// reads can be served from the cache as well
+type Item = (UserKey, UserValue);
 
 #[derive(Eq, std::hash::Hash, PartialEq)]
 pub struct CacheKey(ValueLogId, ValueHandle);
@@ -27,8 +33,8 @@ struct BlobWeighter;
 
 impl Weighter<CacheKey, Item> for BlobWeighter {
     #[allow(clippy::cast_possible_truncation)]
-    fn weight(&self, _: &CacheKey, blob: &Item) -> u64 {
-        blob.len() as u64
+    fn weight(&self, _: &CacheKey, (key, blob): &Item) -> u64 {
+        (key.len() + blob.len()) as u64
     }
 }
 
@@ -73,8 +79,8 @@ impl BlobCache {
         }
     }
 
-    pub(crate) fn insert(&self, key: CacheKey, value: UserValue) {
-        self.data.insert(key, value);
+    pub(crate) fn insert(&self, key: CacheKey, item: Item) {
+        self.data.insert(key, item);
     }
 
     pub(crate) fn get(&self, vlog_id: ValueLogId, vhandle: &ValueHandle) -> Option<Item> {
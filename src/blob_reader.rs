@@ -0,0 +1,361 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    coding::Encode,
+    encryption::EncryptorRef,
+    id::SegmentId,
+    positional_reader::PositionalReader,
+    segment::chunked::{decode_chunk, ChunkTable},
+    Compressor, UserKey, UserValue,
+};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Converts an error into an I/O error, so it can be returned from [`Read`].
+fn into_io_error(e: crate::Error) -> std::io::Error {
+    match e {
+        crate::Error::Io(e) => e,
+        e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+    }
+}
+
+/// Resolves a seek relative to the current position and the value length.
+fn seek_position(pos: u64, len: u64, seek: SeekFrom) -> std::io::Result<u64> {
+    let new_pos = match seek {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::Current(delta) => pos.checked_add_signed(delta),
+        SeekFrom::End(delta) => len.checked_add_signed(delta),
+    };
+
+    new_pos.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
+
+/// Value that is stored as-is, and read directly from the segment file
+pub struct RawBlob {
+    pub(crate) reader: PositionalReader,
+    pub(crate) segment_id: SegmentId,
+
+    /// Offset of the blob in the segment
+    pub(crate) blob_offset: u64,
+
+    /// Offset of the value in the segment
+    pub(crate) value_offset: u64,
+
+    pub(crate) len: u64,
+    pub(crate) pos: u64,
+
+    /// Hashes the value while it is read from start to end,
+    /// so the checksum can be verified when the end is reached
+    ///
+    /// `None` if the checksum is not verified, or the reader was moved.
+    pub(crate) hasher: Option<Xxh3>,
+
+    pub(crate) checksum: u64,
+}
+
+impl RawBlob {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let max = usize::try_from(remaining)
+            .unwrap_or(usize::MAX)
+            .min(buf.len());
+
+        #[allow(clippy::indexing_slicing)]
+        let buf = &mut buf[..max];
+
+        self.reader
+            .seek(SeekFrom::Start(self.value_offset + self.pos))?;
+
+        let n = self.reader.read(buf)?;
+
+        if n == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        }
+
+        self.pos += n as u64;
+
+        if let Some(hasher) = &mut self.hasher {
+            #[allow(clippy::indexing_slicing)]
+            hasher.update(&buf[..n]);
+        }
+
+        if self.pos == self.len {
+            if let Some(hasher) = self.hasher.take() {
+                let got = hasher.digest();
+
+                if got != self.checksum {
+                    return Err(into_io_error(crate::Error::ChecksumMismatch {
+                        segment_id: self.segment_id,
+                        offset: self.blob_offset,
+                        expected: self.checksum,
+                        got,
+                    }));
+                }
+            }
+        }
+
+        Ok(n)
+    }
+}
+
+/// Value that is stored in chunks, which are decoded one at a time
+pub struct ChunkedBlob<C: Compressor + Clone> {
+    pub(crate) reader: PositionalReader,
+    pub(crate) segment_id: SegmentId,
+
+    /// Key of the blob, which the chunks are encrypted with
+    pub(crate) key: UserKey,
+
+    /// Offset of the first chunk in the segment
+    pub(crate) data_offset: u64,
+
+    pub(crate) table: ChunkTable,
+    pub(crate) chunk_offsets: Vec<u64>,
+
+    pub(crate) pos: u64,
+
+    /// Index and data of the decoded chunk
+    pub(crate) current: Option<(usize, Vec<u8>)>,
+
+    pub(crate) compression: Option<C>,
+    pub(crate) encryption: Option<(EncryptorRef, u32)>,
+    pub(crate) verify_checksums: bool,
+
+    /// Offset of the blob in the segment
+    pub(crate) blob_offset: u64,
+
+    /// Hashes the chunks while they are loaded in order,
+    /// so the blob checksum can be verified when the last chunk is loaded
+    ///
+    /// `None` if the checksum is not verified, or a chunk was skipped.
+    pub(crate) hasher: Option<Xxh3>,
+
+    /// Index of the chunk that needs to be hashed next
+    pub(crate) next_hashed_chunk: usize,
+
+    pub(crate) checksum: u64,
+}
+
+impl<C: Compressor + Clone> ChunkedBlob<C> {
+    /// Adds a chunk to the blob checksum, and verifies the checksum
+    /// once the last chunk has been hashed.
+    ///
+    /// The blob checksum covers the chunks in order, so it can only be
+    /// verified if every chunk is loaded exactly once, from first to last.
+    fn hash_chunk(&mut self, idx: usize, stored: &[u8]) -> crate::Result<()> {
+        if idx != self.next_hashed_chunk {
+            self.hasher = None;
+        }
+
+        let Some(hasher) = &mut self.hasher else {
+            return Ok(());
+        };
+
+        hasher.update(stored);
+        self.next_hashed_chunk += 1;
+
+        if self.next_hashed_chunk == self.table.chunks.len() {
+            if let Some(mut hasher) = self.hasher.take() {
+                // NOTE: The chunk table is hashed after the chunks, see `chunked::checksum`
+                hasher.update(&self.table.encode_into_vec()?);
+                let got = hasher.digest();
+
+                if got != self.checksum {
+                    return Err(crate::Error::ChecksumMismatch {
+                        segment_id: self.segment_id,
+                        offset: self.blob_offset,
+                        expected: self.checksum,
+                        got,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_chunk(&mut self, idx: usize) -> crate::Result<Vec<u8>> {
+        let (Some(chunk), Some(offset)) = (self.table.chunks.get(idx), self.chunk_offsets.get(idx))
+        else {
+            return Err(crate::Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        };
+
+        let offset = self.data_offset + offset;
+
+        let mut stored = vec![0; chunk.stored_len as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut stored)?;
+
+        if self.verify_checksums {
+            let got = xxhash_rust::xxh3::xxh3_64(&stored);
+
+            if got != chunk.checksum {
+                return Err(crate::Error::ChecksumMismatch {
+                    segment_id: self.segment_id,
+                    offset,
+                    expected: chunk.checksum,
+                    got,
+                });
+            }
+        }
+
+        self.hash_chunk(idx, &stored)?;
+
+        // NOTE: The chunk index is limited by the chunk count (u32)
+        #[allow(clippy::cast_possible_truncation)]
+        decode_chunk(
+            &self.table,
+            idx as u32,
+            stored,
+            &self.key,
+            self.segment_id,
+            self.compression.as_ref(),
+            self.encryption.as_ref(),
+        )
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.table.value_len.into() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let chunk_size = u64::from(self.table.chunk_size);
+
+        // NOTE: The chunk index is limited by the chunk count (u32)
+        #[allow(clippy::cast_possible_truncation)]
+        let idx = (self.pos / chunk_size) as usize;
+
+        if self.current.as_ref().map(|(x, _)| *x) != Some(idx) {
+            let data = self.load_chunk(idx).map_err(into_io_error)?;
+            self.current = Some((idx, data));
+        }
+
+        let Some((_, data)) = &self.current else {
+            return Ok(0);
+        };
+
+        // NOTE: Chunks are tiny
+        #[allow(clippy::cast_possible_truncation)]
+        let within = (self.pos % chunk_size) as usize;
+
+        let Some(available) = data.get(within..).filter(|x| !x.is_empty()) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "chunk is shorter than expected",
+            ));
+        };
+
+        let n = available.len().min(buf.len());
+
+        #[allow(clippy::indexing_slicing)]
+        buf[..n].copy_from_slice(&available[..n]);
+
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Inner<C: Compressor + Clone> {
+    Raw(RawBlob),
+    Chunked(ChunkedBlob<C>),
+
+    /// Value that cannot be streamed, so it is held in memory
+    Buffered(Cursor<UserValue>),
+}
+
+/// Streams the value of a blob
+///
+/// See [`ValueLog::open_blob`](crate::ValueLog::open_blob).
+pub struct BlobReader<C: Compressor + Clone>(Inner<C>);
+
+impl<C: Compressor + Clone> From<RawBlob> for BlobReader<C> {
+    fn from(value: RawBlob) -> Self {
+        Self(Inner::Raw(value))
+    }
+}
+
+impl<C: Compressor + Clone> From<ChunkedBlob<C>> for BlobReader<C> {
+    fn from(value: ChunkedBlob<C>) -> Self {
+        Self(Inner::Chunked(value))
+    }
+}
+
+impl<C: Compressor + Clone> From<UserValue> for BlobReader<C> {
+    fn from(value: UserValue) -> Self {
+        Self(Inner::Buffered(Cursor::new(value)))
+    }
+}
+
+impl<C: Compressor + Clone> BlobReader<C> {
+    /// Returns the length of the value.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        match &self.0 {
+            Inner::Raw(blob) => blob.len,
+            Inner::Chunked(blob) => blob.table.value_len.into(),
+            Inner::Buffered(cursor) => cursor.get_ref().len() as u64,
+        }
+    }
+
+    /// Returns `true` if the value is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the value is streamed from disk,
+    /// instead of being held in memory.
+    #[must_use]
+    pub fn is_streaming(&self) -> bool {
+        !matches!(self.0, Inner::Buffered(_))
+    }
+}
+
+impl<C: Compressor + Clone> Read for BlobReader<C> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            Inner::Raw(blob) => blob.read(buf),
+            Inner::Chunked(blob) => blob.read(buf),
+            Inner::Buffered(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl<C: Compressor + Clone> Seek for BlobReader<C> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let len = self.len();
+
+        match &mut self.0 {
+            Inner::Raw(blob) => {
+                let new_pos = seek_position(blob.pos, len, pos)?;
+
+                // NOTE: The checksum can only be verified if the value is read in one go
+                if new_pos != blob.pos {
+                    blob.hasher = None;
+                }
+
+                blob.pos = new_pos;
+                Ok(new_pos)
+            }
+            Inner::Chunked(blob) => {
+                blob.pos = seek_position(blob.pos, len, pos)?;
+                Ok(blob.pos)
+            }
+            Inner::Buffered(cursor) => cursor.seek(pos),
+        }
+    }
+}
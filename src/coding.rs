@@ -24,6 +24,9 @@ pub enum DecodeError {
 
     /// Invalid block header
     InvalidHeader(&'static str),
+
+    /// Record checksum did not match (record type, offset)
+    InvalidChecksum((&'static str, u64)),
 }
 
 impl From<std::io::Error> for EncodeError {
@@ -0,0 +1,51 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{CompressionType, Compressor};
+use byteorder::{LittleEndian, ReadBytesExt};
+
+/// LZ4 compressor, using [`lz4_flex`](https://github.com/PSeitz/lz4_flex)
+///
+/// Fast compression with a moderate compression ratio.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lz4Compressor;
+
+impl Lz4Compressor {
+    /// Compression type ID of LZ4
+    pub const COMPRESSION_TYPE_ID: u8 = 1;
+}
+
+/// Every byte of an LZ4 block decompresses into at most 255 bytes
+const MAX_DECOMPRESSION_RATIO: usize = 255;
+
+impl Compressor for Lz4Compressor {
+    fn compress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+        Ok(lz4_flex::compress_prepend_size(bytes))
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+        let mut block = bytes;
+
+        let size = block
+            .read_u32::<LittleEndian>()
+            .map_err(|e| crate::Error::Decompress(e.into()))? as usize;
+
+        // IMPORTANT: The size prefix is read from disk, so it needs to be
+        // checked before allocating the output buffer
+        if size > block.len().saturating_mul(MAX_DECOMPRESSION_RATIO) {
+            return Err(crate::Error::Decompress(
+                format!("size prefix {size} is too large for {} bytes", block.len()).into(),
+            ));
+        }
+
+        lz4_flex::block::decompress(block, size).map_err(|e| crate::Error::Decompress(e.into()))
+    }
+
+    fn compression_type(&self) -> CompressionType {
+        CompressionType {
+            id: Self::COMPRESSION_TYPE_ID,
+            params: vec![],
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+#[cfg(feature = "lz4")]
+mod lz4;
+
+#[cfg(feature = "zstd")]
+mod zstd;
+
+#[cfg(feature = "lz4")]
+pub use lz4::Lz4Compressor;
+
+#[cfg(feature = "zstd")]
+pub use self::zstd::ZstdCompressor;
+
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+/// Identifies the compression scheme a segment was written with
+///
+/// The compression type is stored in the segment's metadata, so
+/// the correct decompressor can be chosen for every segment.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct CompressionType {
+    /// Compression scheme ID
+    ///
+    /// ID 0 means the compression is unspecified, which is used by
+    /// compressors that do not describe themselves.
+    ///
+    /// The built-in compressors use ID 1 (LZ4) and 2 (Zstd).
+    pub id: u8,
+
+    /// Scheme-specific parameters
+    pub params: Vec<u8>,
+}
+
+impl Encode for CompressionType {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        writer.write_u8(self.id)?;
+
+        // NOTE: Parameters are tiny, u16 is plenty
+        #[allow(clippy::cast_possible_truncation)]
+        writer.write_u16::<BigEndian>(self.params.len() as u16)?;
+        writer.write_all(&self.params)?;
+
+        Ok(())
+    }
+}
+
+impl Decode for CompressionType {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        let id = reader.read_u8()?;
+
+        let params_len = reader.read_u16::<BigEndian>()?;
+        let mut params = vec![0; params_len.into()];
+        reader.read_exact(&mut params)?;
+
+        Ok(Self { id, params })
+    }
+}
+
+/// Generic compression trait
+pub trait Compressor {
+    /// Compresses a value
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if compression fails.
+    fn compress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>>;
+
+    /// Decompresses a value
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if decompression fails.
+    fn decompress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>>;
+
+    /// Returns the compression type that is recorded in segments
+    /// written with this compressor.
+    ///
+    /// Segments are only decompressed by a compressor that accepts their
+    /// compression type (see [`Compressor::decompressor_for`]).
+    ///
+    /// Defaults to the unspecified compression type (ID 0). Segments of this type
+    /// are accepted by any compressor that does not describe itself either, so
+    /// switching between such compressors is not detected. Override this to
+    /// safely change or mix compressors.
+    fn compression_type(&self) -> CompressionType {
+        CompressionType::default()
+    }
+
+    /// Returns a compressor that can decompress segments written
+    /// with the given compression type.
+    ///
+    /// Implementing this allows a value log to contain segments that were
+    /// written with different compressors (or compression parameters),
+    /// e.g. after changing the configured compression.
+    ///
+    /// Returns `None` if the compression type is not supported, which is the
+    /// default for any compression type other than [`Compressor::compression_type`].
+    fn decompressor_for(&self, compression_type: &CompressionType) -> Option<Self>
+    where
+        Self: Sized + Clone,
+    {
+        (compression_type == &self.compression_type()).then(|| self.clone())
+    }
+
+    /// Returns the compressor that new segments are written with.
+    ///
+    /// Compressors with state that may change over time (e.g. a trained dictionary)
+    /// can pin it here, so all blobs of a segment are compressed the same way.
+    ///
+    /// Defaults to a clone of the compressor.
+    #[must_use]
+    fn for_new_segments(&self) -> Self
+    where
+        Self: Sized + Clone,
+    {
+        self.clone()
+    }
+
+    /// Loads persisted compressor state (e.g. dictionaries) from the value log folder.
+    ///
+    /// Called once when a value log is opened, before its segments are recovered.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn load_state(&mut self, folder: &Path) -> crate::Result<()> {
+        let _ = folder;
+        Ok(())
+    }
+}
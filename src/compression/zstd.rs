@@ -0,0 +1,332 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{CompressionType, Compressor};
+use crate::{manifest::rewrite_atomic, segment::merge::MergeReader, ValueLog};
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+/// Folder (inside the value log folder) that dictionaries are stored in
+pub const DICTIONARIES_FOLDER: &str = "dictionaries";
+
+/// Trained Zstd dictionary
+struct Dictionary {
+    id: u32,
+    raw: Vec<u8>,
+    decoder: DecoderDictionary<'static>,
+}
+
+impl Dictionary {
+    fn new(id: u32, raw: Vec<u8>) -> Self {
+        let decoder = DecoderDictionary::copy(&raw);
+        Self { id, raw, decoder }
+    }
+}
+
+/// Dictionary a compressor is pinned to
+#[derive(Clone)]
+struct PinnedDictionary {
+    dictionary: Arc<Dictionary>,
+
+    /// Only prepared for compressors that write new segments
+    encoder: Option<Arc<EncoderDictionary<'static>>>,
+}
+
+type Dictionaries = BTreeMap<u32, Arc<Dictionary>>;
+
+/// Zstd compressor, using [`zstd`](https://github.com/gyscos/zstd-rs)
+///
+/// Slower than LZ4, but achieves a better compression ratio.
+///
+/// Small values can be compressed much better using a trained dictionary,
+/// see [`ValueLog::train_zstd_dictionary`].
+#[derive(Clone)]
+pub struct ZstdCompressor {
+    /// Compression level
+    ///
+    /// Higher levels compress better, but slower.
+    /// Decompression speed is mostly unaffected by the level.
+    pub level: i32,
+
+    dictionary: Option<PinnedDictionary>,
+
+    /// Dictionaries of the value log this compressor is used in
+    dictionaries: Arc<RwLock<Dictionaries>>,
+}
+
+impl std::fmt::Debug for ZstdCompressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZstdCompressor")
+            .field("level", &self.level)
+            .field("dictionary", &self.dictionary_id())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ZstdCompressor {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+impl ZstdCompressor {
+    /// Compression type ID of Zstd
+    pub const COMPRESSION_TYPE_ID: u8 = 2;
+
+    /// Creates a new Zstd compressor with the given compression level.
+    #[must_use]
+    pub fn new(level: i32) -> Self {
+        Self {
+            level,
+            dictionary: None,
+            dictionaries: Arc::default(),
+        }
+    }
+
+    /// Returns the ID of the dictionary the compressor uses, if any.
+    #[must_use]
+    pub fn dictionary_id(&self) -> Option<u32> {
+        self.dictionary.as_ref().map(|x| x.dictionary.id)
+    }
+
+    fn with_dictionary(&self, dictionary: Option<PinnedDictionary>) -> Self {
+        Self {
+            level: self.level,
+            dictionary,
+            dictionaries: self.dictionaries.clone(),
+        }
+    }
+}
+
+impl Compressor for ZstdCompressor {
+    fn compress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+        let result = match &self.dictionary {
+            Some(PinnedDictionary {
+                encoder: Some(encoder),
+                ..
+            }) => zstd::bulk::Compressor::with_prepared_dictionary(encoder)
+                .and_then(|mut x| x.compress(bytes)),
+            Some(PinnedDictionary { dictionary, .. }) => {
+                zstd::bulk::Compressor::with_dictionary(self.level, &dictionary.raw)
+                    .and_then(|mut x| x.compress(bytes))
+            }
+            None => zstd::bulk::compress(bytes, self.level),
+        };
+
+        result.map_err(|e| crate::Error::Compress(e.into()))
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+        let result = match &self.dictionary {
+            Some(PinnedDictionary { dictionary, .. }) => {
+                zstd::stream::Decoder::with_prepared_dictionary(bytes, &dictionary.decoder)
+                    .and_then(|mut x| {
+                        let mut buf = vec![];
+                        x.read_to_end(&mut buf).map(|_| buf)
+                    })
+            }
+            None => zstd::stream::decode_all(bytes),
+        };
+
+        result.map_err(|e| crate::Error::Decompress(e.into()))
+    }
+
+    fn compression_type(&self) -> CompressionType {
+        let mut params = self.level.to_be_bytes().to_vec();
+
+        if let Some(id) = self.dictionary_id() {
+            params.extend_from_slice(&id.to_be_bytes());
+        }
+
+        CompressionType {
+            id: Self::COMPRESSION_TYPE_ID,
+            params,
+        }
+    }
+
+    fn decompressor_for(&self, compression_type: &CompressionType) -> Option<Self> {
+        if compression_type.id != Self::COMPRESSION_TYPE_ID {
+            return None;
+        }
+
+        // NOTE: The level does not matter for decompression,
+        // but we keep it so the compression type is reported correctly
+        let params = compression_type.params.as_slice();
+
+        let (level, dictionary_id) = match params.len() {
+            4 => (params, None),
+            8 => {
+                let (level, dictionary_id) = params.split_at(4);
+                (level, Some(dictionary_id))
+            }
+            _ => return None,
+        };
+
+        let dictionary = match dictionary_id {
+            Some(id) => {
+                let id = u32::from_be_bytes(id.try_into().ok()?);
+
+                let dictionary = self
+                    .dictionaries
+                    .read()
+                    .expect("lock is poisoned")
+                    .get(&id)?
+                    .clone();
+
+                Some(PinnedDictionary {
+                    dictionary,
+                    encoder: None,
+                })
+            }
+            None => None,
+        };
+
+        let mut compressor = self.with_dictionary(dictionary);
+        compressor.level = i32::from_be_bytes(level.try_into().ok()?);
+        Some(compressor)
+    }
+
+    fn for_new_segments(&self) -> Self {
+        // NOTE: Always use the newest dictionary
+        let dictionary = self
+            .dictionaries
+            .read()
+            .expect("lock is poisoned")
+            .values()
+            .next_back()
+            .cloned();
+
+        self.with_dictionary(dictionary.map(|dictionary| PinnedDictionary {
+            encoder: Some(Arc::new(EncoderDictionary::copy(
+                &dictionary.raw,
+                self.level,
+            ))),
+            dictionary,
+        }))
+    }
+
+    fn load_state(&mut self, folder: &Path) -> crate::Result<()> {
+        let folder = folder.join(DICTIONARIES_FOLDER);
+
+        let mut items = BTreeMap::new();
+
+        if folder.try_exists()? {
+            for dirent in std::fs::read_dir(&folder)? {
+                let dirent = dirent?;
+                let file_name = dirent.file_name();
+
+                // NOTE: Skip leftover temporary files of unfinished writes
+                let Some(id) = file_name.to_str().and_then(|x| x.parse::<u32>().ok()) else {
+                    continue;
+                };
+
+                log::trace!("Loading zstd dictionary {id}");
+
+                let raw = std::fs::read(dirent.path())?;
+                items.insert(id, Arc::new(Dictionary::new(id, raw)));
+            }
+        }
+
+        // NOTE: Every value log gets its own dictionaries, even if
+        // the compressor was cloned from another value log's config
+        self.dictionary = None;
+        self.dictionaries = Arc::new(RwLock::new(items));
+
+        Ok(())
+    }
+}
+
+impl ValueLog<ZstdCompressor> {
+    /// Trains a Zstd dictionary from a sample of the stored blobs.
+    ///
+    /// The dictionary is persisted in the value log folder and is used to compress
+    /// all segments that are written afterwards.
+    /// Existing segments keep using the dictionary they were written with,
+    /// until they are rewritten by garbage collection.
+    ///
+    /// Returns the ID of the new dictionary.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or training fails
+    /// (e.g. because there are not enough samples).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::significant_drop_tightening)]
+    pub fn train_zstd_dictionary(&self, max_samples: usize, max_size: usize) -> crate::Result<u32> {
+        let readers = self
+            .manifest
+            .segments
+            .read()
+            .expect("lock is poisoned")
+            .values()
+            .map(|x| self.scan_segment(x))
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let item_count = self
+            .manifest
+            .list_segments()
+            .iter()
+            .map(|x| x.meta.item_count)
+            .sum::<u64>();
+
+        let max_samples = max_samples.max(1);
+
+        // NOTE: Spread the samples over all blobs
+        let step = usize::try_from(item_count / max_samples as u64)
+            .unwrap_or(usize::MAX)
+            .max(1);
+
+        let mut samples = Vec::with_capacity(max_samples);
+
+        for (idx, item) in MergeReader::new(readers).enumerate() {
+            let (_, value, _, _, _) = item?;
+
+            if idx % step == 0 {
+                samples.push(value);
+            }
+
+            if samples.len() >= max_samples {
+                break;
+            }
+        }
+
+        log::debug!("Training zstd dictionary from {} samples", samples.len());
+
+        let raw = zstd::dict::from_samples(&samples, max_size)?;
+
+        let mut dictionaries = self
+            .config
+            .compression
+            .dictionaries
+            .write()
+            .expect("lock is poisoned");
+
+        let id = dictionaries.keys().next_back().map_or(1, |&id| id + 1);
+
+        let folder = self.path.join(DICTIONARIES_FOLDER);
+
+        std::fs::create_dir_all(&folder)?;
+        rewrite_atomic(folder.join(id.to_string()), &raw)?;
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            let folder = std::fs::File::open(&folder)?;
+            folder.sync_all()?;
+        }
+
+        log::info!("Trained zstd dictionary {id} ({} bytes)", raw.len());
+
+        dictionaries.insert(id, Arc::new(Dictionary::new(id, raw)));
+
+        Ok(id)
+    }
+}
@@ -2,9 +2,31 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{blob_cache::BlobCache, compression::Compressor};
+use crate::{
+    blob_cache::BlobCache,
+    compression::Compressor,
+    descriptor_table::DescriptorTable,
+    encryption::{Encryptor, EncryptorRef},
+};
 use std::sync::Arc;
 
+/// Controls when blob checksums are verified on the read path
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ChecksumVerification {
+    /// Every read goes to disk and is verified, bypassing the blob cache
+    Always,
+
+    /// Blobs are verified when they are read from disk
+    ///
+    /// Blobs served from the blob cache have already been verified when
+    /// they were loaded.
+    #[default]
+    OnCacheMiss,
+
+    /// Checksums are never verified on reads
+    Never,
+}
+
 /// Value log configuration
 pub struct Config<C: Compressor + Clone> {
     /// Target size of vLog segments
@@ -13,8 +35,23 @@ pub struct Config<C: Compressor + Clone> {
     /// Blob cache to use
     pub(crate) blob_cache: Arc<BlobCache>,
 
+    /// File descriptor cache to use
+    pub(crate) descriptor_table: Arc<DescriptorTable>,
+
     /// Compression to use
     pub(crate) compression: C,
+
+    /// Encryption to use
+    pub(crate) encryption: Option<EncryptorRef>,
+
+    /// When to verify blob checksums on reads
+    pub(crate) checksum_verification: ChecksumVerification,
+
+    /// Minimum ratio compression needs to save to store a blob compressed
+    pub(crate) min_compression_savings: f32,
+
+    /// Size above which blobs are not cached, and stored in chunks
+    pub(crate) large_blob_threshold: u64,
 }
 
 impl<C: Compressor + Clone + Default> Default for Config<C> {
@@ -24,7 +61,12 @@ impl<C: Compressor + Clone + Default> Default for Config<C> {
             blob_cache: Arc::new(BlobCache::with_capacity_bytes(
                 /* 16 MiB */ 16 * 1_024 * 1_024,
             )),
+            descriptor_table: Arc::new(DescriptorTable::new(64)),
             compression: C::default(),
+            encryption: None,
+            checksum_verification: ChecksumVerification::default(),
+            min_compression_savings: 0.0,
+            large_blob_threshold: /* 1 MiB */ 1_024 * 1_024,
         }
     }
 }
@@ -37,6 +79,20 @@ impl<C: Compressor + Clone> Config<C> {
         self
     }
 
+    /// Sets the encryption scheme.
+    ///
+    /// Blobs are encrypted after being compressed.
+    ///
+    /// Value logs that contain encrypted segments can
+    /// only be opened with an encryptor.
+    ///
+    /// Default = none
+    #[must_use]
+    pub fn encryption(mut self, encryptor: Arc<dyn Encryptor + Send + Sync>) -> Self {
+        self.encryption = Some(encryptor);
+        self
+    }
+
     /// Sets the blob cache.
     ///
     /// You can create a global [`BlobCache`] and share it between multiple
@@ -49,6 +105,18 @@ impl<C: Compressor + Clone> Config<C> {
         self
     }
 
+    /// Sets the file descriptor cache.
+    ///
+    /// You can create a global [`DescriptorTable`] and share it between multiple
+    /// value logs to cap the global amount of open files.
+    ///
+    /// Defaults to a descriptor table with 64 open files *per value log*.
+    #[must_use]
+    pub fn descriptor_table(mut self, descriptor_table: Arc<DescriptorTable>) -> Self {
+        self.descriptor_table = descriptor_table;
+        self
+    }
+
     /// Sets the maximum size of value log segments.
     ///
     /// This heavily influences space amplification, as
@@ -62,4 +130,53 @@ impl<C: Compressor + Clone> Config<C> {
         self.segment_size_bytes = bytes;
         self
     }
+
+    /// Sets when blob checksums are verified in [`ValueLog::get`](crate::ValueLog::get).
+    ///
+    /// Default = [`ChecksumVerification::OnCacheMiss`]
+    #[must_use]
+    pub fn checksum_verification(mut self, mode: ChecksumVerification) -> Self {
+        self.checksum_verification = mode;
+        self
+    }
+
+    /// Sets the minimum ratio of space that compression needs to save
+    /// for a blob to be stored compressed.
+    ///
+    /// Blobs that do not compress well enough (e.g. already compressed images)
+    /// are stored raw, so reading them does not need to decompress them.
+    ///
+    /// For example, `0.1` requires the compressed blob to be at most
+    /// 90% of the size of the raw blob.
+    ///
+    /// Default = 0.0 (blobs are stored raw if compression makes them larger)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ratio is invalid.
+    #[must_use]
+    pub fn min_compression_savings(mut self, ratio: f32) -> Self {
+        assert!(
+            ratio.is_finite() && ratio.is_sign_positive(),
+            "invalid compression savings ratio"
+        );
+        self.min_compression_savings = ratio.min(1.0);
+        self
+    }
+
+    /// Sets the value size above which blobs are considered large.
+    ///
+    /// Large blobs are not inserted into the blob cache, so streaming a few large
+    /// values does not evict many small ones.
+    ///
+    /// If compression or encryption is used, large blobs are stored in independently
+    /// compressed (and encrypted) chunks, so they can be streamed using
+    /// [`ValueLog::open_blob`](crate::ValueLog::open_blob) without being read into memory.
+    ///
+    /// Default = 1 MiB
+    #[must_use]
+    pub fn large_blob_threshold(mut self, bytes: u64) -> Self {
+        self.large_blob_threshold = bytes;
+        self
+    }
 }
@@ -0,0 +1,91 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{id::SegmentId, value_log::ValueLogId};
+use quick_cache::{sync::Cache, UnitWeighter};
+use std::{fs::File, path::Path, sync::Arc};
+
+type Item = Arc<File>;
+
+/// Caches open file descriptors of segment files
+///
+/// This avoids having to open a segment file for every blob that is
+/// not found in the [`BlobCache`](crate::BlobCache).
+///
+/// File descriptors are shared, and read from using positional reads,
+/// so concurrent readers of the same segment do not interfere with each other.
+pub struct DescriptorTable {
+    // NOTE: rustc_hash performed best: https://fjall-rs.github.io/post/fjall-2-1
+    /// Concurrent cache implementation
+    data: Cache<(ValueLogId, SegmentId), Item, UnitWeighter, rustc_hash::FxBuildHasher>,
+
+    /// Capacity in file descriptors
+    capacity: usize,
+}
+
+impl std::fmt::Debug for DescriptorTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DescriptorTable<cap: {} files>", self.capacity)
+    }
+}
+
+impl DescriptorTable {
+    /// Creates a new descriptor table that keeps up to `n` files open.
+    #[must_use]
+    pub fn new(n: usize) -> Self {
+        use quick_cache::sync::DefaultLifecycle;
+
+        #[allow(clippy::default_trait_access)]
+        let quick_cache = Cache::with(
+            n,
+            n as u64,
+            UnitWeighter,
+            Default::default(),
+            DefaultLifecycle::default(),
+        );
+
+        Self {
+            data: quick_cache,
+            capacity: n,
+        }
+    }
+
+    /// Returns the file of the given segment, opening it if needed.
+    pub(crate) fn access<P: AsRef<Path>>(
+        &self,
+        vlog_id: ValueLogId,
+        segment_id: SegmentId,
+        path: P,
+    ) -> std::io::Result<Item> {
+        self.data.get_or_insert_with(&(vlog_id, segment_id), || {
+            log::trace!("Opening vLog segment file #{segment_id}");
+            File::open(path).map(Arc::new)
+        })
+    }
+
+    /// Closes the file of the given segment.
+    ///
+    /// Readers that currently use the file keep it open until they are done.
+    pub(crate) fn remove(&self, vlog_id: ValueLogId, segment_id: SegmentId) {
+        self.data.remove(&(vlog_id, segment_id));
+    }
+
+    /// Returns the maximum amount of open files.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the amount of open files.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if there are no open files.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
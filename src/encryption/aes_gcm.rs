@@ -0,0 +1,99 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::Encryptor;
+use crate::HashMap;
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+
+const NONCE_SIZE: usize = 12;
+
+/// AES-256-GCM encryptor, using [`aes-gcm`](https://github.com/RustCrypto/AEADs)
+///
+/// Every blob is encrypted using a random nonce, which is
+/// stored in front of the ciphertext.
+///
+/// The associated data (see [`Encryptor`]) is authenticated, but not stored.
+pub struct AesGcmEncryptor {
+    key_id: u32,
+    ciphers: HashMap<u32, Aes256Gcm>,
+}
+
+impl AesGcmEncryptor {
+    /// Creates a new encryptor that encrypts new segments with the given key.
+    #[must_use]
+    pub fn new(key_id: u32, key: &[u8; 32]) -> Self {
+        Self {
+            key_id,
+            ciphers: HashMap::default(),
+        }
+        .with_retired_key(key_id, key)
+    }
+
+    /// Adds an older key that is only used to decrypt existing segments.
+    #[must_use]
+    pub fn with_retired_key(mut self, key_id: u32, key: &[u8; 32]) -> Self {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        self.ciphers.insert(key_id, cipher);
+        self
+    }
+
+    fn unknown_key(key_id: u32) -> Box<dyn std::error::Error + Send + Sync> {
+        format!("unknown encryption key {key_id}").into()
+    }
+}
+
+impl Encryptor for AesGcmEncryptor {
+    fn key_id(&self) -> u32 {
+        self.key_id
+    }
+
+    fn encrypt(&self, key_id: u32, bytes: &[u8], associated_data: &[u8]) -> crate::Result<Vec<u8>> {
+        let cipher = self
+            .ciphers
+            .get(&key_id)
+            .ok_or_else(|| crate::Error::Encrypt(Self::unknown_key(key_id)))?;
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: bytes,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|e| crate::Error::Encrypt(e.into()))?;
+
+        let mut result = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        result.extend_from_slice(&nonce);
+        result.extend_from_slice(&ciphertext);
+        Ok(result)
+    }
+
+    fn decrypt(&self, key_id: u32, bytes: &[u8], associated_data: &[u8]) -> crate::Result<Vec<u8>> {
+        let cipher = self
+            .ciphers
+            .get(&key_id)
+            .ok_or_else(|| crate::Error::Decrypt(Self::unknown_key(key_id)))?;
+
+        if bytes.len() < NONCE_SIZE {
+            return Err(crate::Error::Decrypt("ciphertext is too short".into()));
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_SIZE);
+
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|e| crate::Error::Decrypt(e.into()))
+    }
+}
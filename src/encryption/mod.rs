@@ -0,0 +1,60 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+#[cfg(feature = "aes-gcm")]
+mod aes_gcm;
+
+#[cfg(feature = "aes-gcm")]
+pub use aes_gcm::AesGcmEncryptor;
+
+use crate::id::SegmentId;
+use std::sync::Arc;
+
+/// Generic encryption trait
+///
+/// Blobs are encrypted after compression, so compression is still effective.
+///
+/// Every segment is encrypted with a single key, whose ID is stored in the
+/// segment's metadata.
+/// To rotate keys, make [`Encryptor::key_id`] return a new key ID (while still
+/// being able to decrypt using older keys) and roll over segments that use an old key.
+///
+/// Every value (or chunk of a value) is encrypted with associated data that
+/// identifies the blob key, the segment and the chunk index, which needs to be
+/// authenticated, so ciphertexts cannot be swapped between blobs or chunks.
+pub trait Encryptor {
+    /// Returns the ID of the key that new segments are encrypted with.
+    fn key_id(&self) -> u32;
+
+    /// Encrypts a value using the given key, authenticating the associated data
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if encryption fails.
+    fn encrypt(&self, key_id: u32, bytes: &[u8], associated_data: &[u8]) -> crate::Result<Vec<u8>>;
+
+    /// Decrypts a value using the given key, authenticating the associated data
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if decryption fails, e.g. because the key is unknown,
+    /// or the associated data does not match.
+    fn decrypt(&self, key_id: u32, bytes: &[u8], associated_data: &[u8]) -> crate::Result<Vec<u8>>;
+}
+
+/// Shared encryptor
+pub type EncryptorRef = Arc<dyn Encryptor + Send + Sync>;
+
+/// Returns the associated data a value is encrypted with.
+///
+/// `chunk_idx` is `None` if the value is not stored in chunks.
+pub fn associated_data(key: &[u8], segment_id: SegmentId, chunk_idx: Option<u32>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(
+        std::mem::size_of::<SegmentId>() + std::mem::size_of::<u32>() + key.len(),
+    );
+    bytes.extend_from_slice(&segment_id.to_be_bytes());
+    bytes.extend_from_slice(&chunk_idx.unwrap_or(u32::MAX).to_be_bytes());
+    bytes.extend_from_slice(key);
+    bytes
+}
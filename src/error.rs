@@ -4,6 +4,8 @@
 
 use crate::{
     coding::{DecodeError, EncodeError},
+    compression::CompressionType,
+    id::SegmentId,
     version::Version,
 };
 
@@ -24,13 +26,51 @@ pub enum Error {
     Decode(DecodeError),
 
     /// Compression failed
-    Compress,
+    Compress(Box<dyn std::error::Error + Send + Sync>),
 
     /// Decompression failed
-    Decompress,
-    // TODO:
-    // /// Checksum check failed
-    // ChecksumMismatch,
+    Decompress(Box<dyn std::error::Error + Send + Sync>),
+
+    /// Encryption failed
+    Encrypt(Box<dyn std::error::Error + Send + Sync>),
+
+    /// Decryption failed
+    Decrypt(Box<dyn std::error::Error + Send + Sync>),
+
+    /// A segment was written with a compression type
+    /// that the configured compressor cannot decompress
+    UnsupportedCompression(CompressionType),
+
+    /// The value log contains encrypted segments,
+    /// but no encryptor is configured
+    MissingEncryptor,
+
+    /// Checksum check failed
+    ChecksumMismatch {
+        /// Segment the blob is stored in
+        segment_id: SegmentId,
+
+        /// Offset of the blob in the segment
+        offset: u64,
+
+        /// Checksum stored in the blob header
+        expected: u64,
+
+        /// Checksum computed over the read data
+        got: u64,
+    },
+
+    /// The key stored in a blob did not match the requested key
+    ///
+    /// This indicates the value handle does not belong to the key,
+    /// e.g. because the index is corrupted.
+    KeyMismatch {
+        /// Segment the blob is stored in
+        segment_id: SegmentId,
+
+        /// Offset of the blob in the segment
+        offset: u64,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -39,7 +79,17 @@ impl std::fmt::Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Compress(e) | Self::Decompress(e) | Self::Encrypt(e) | Self::Decrypt(e) => {
+                Some(&**e)
+            }
+            _ => None,
+        }
+    }
+}
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
@@ -2,9 +2,12 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+pub mod plan;
 pub mod report;
+pub mod rollover;
+pub mod worker;
 
-use crate::{id::SegmentId, Compressor, ValueLog};
+use crate::{id::SegmentId, time::unix_timestamp_millis, Compressor, ValueLog};
 
 /// GC strategy
 #[allow(clippy::module_name_repetitions)]
@@ -121,3 +124,113 @@ impl<C: Compressor + Clone> GcStrategy<C> for SpaceAmpStrategy {
         }
     }
 }
+
+/// Picks the segments that give the most benefit for the cost of rewriting them,
+/// using the cost-benefit heuristic of log-structured file systems:
+///
+/// `benefit / cost = (1 - u) * age / (1 + u)`
+///
+/// where `u` is the ratio of live bytes in a segment.
+///
+/// Compared to [`SpaceAmpStrategy`], old segments are preferred over young segments
+/// with the same amount of stale data, because young segments are likely to become
+/// even more stale soon, while cold data is better packed together.
+pub struct CostBenefitStrategy(usize);
+
+impl CostBenefitStrategy {
+    /// Creates a new strategy that picks up to `max_segments` segments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_segments` is 0.
+    #[must_use]
+    pub fn new(max_segments: usize) -> Self {
+        assert!(max_segments > 0, "invalid segment count");
+        Self(max_segments)
+    }
+}
+
+impl<C: Compressor + Clone> GcStrategy<C> for CostBenefitStrategy {
+    #[allow(clippy::cast_precision_loss, clippy::significant_drop_tightening)]
+    fn pick(&self, value_log: &ValueLog<C>) -> Vec<SegmentId> {
+        let now = unix_timestamp_millis();
+
+        let lock = value_log
+            .manifest
+            .segments
+            .read()
+            .expect("lock is poisoned");
+
+        let mut candidates = lock
+            .values()
+            .filter(|x| !x.is_stale())
+            .filter(|x| x.gc_stats.stale_bytes() > 0)
+            .map(|x| {
+                let total_bytes = x.meta.total_uncompressed_bytes.max(1) as f64;
+                let stale_bytes = x.gc_stats.stale_bytes() as f64;
+                let utilization = 1.0 - (stale_bytes / total_bytes).min(1.0);
+
+                // NOTE: Clock may have gone backwards
+                let age = now.saturating_sub(x.created_at()) as f64;
+
+                let score = (1.0 - utilization) * age / (1.0 + utilization);
+                (x.id, score)
+            })
+            .collect::<Vec<_>>();
+
+        // Sort by score descending
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        candidates
+            .into_iter()
+            .take(self.0)
+            .map(|(id, score)| {
+                log::debug!("Selected segment #{id} for GC (cost-benefit score={score})");
+                id
+            })
+            .collect()
+    }
+}
+
+/// Picks segments that have a certain percentage of expired blobs
+///
+/// The expired ratio is computed from the expiry timestamps stored in the
+/// segment metadata, so it does not require scanning the index.
+pub struct ExpiredRatioStrategy(f32);
+
+impl ExpiredRatioStrategy {
+    /// Creates a new strategy with the given threshold.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ratio is invalid.
+    #[must_use]
+    pub fn new(ratio: f32) -> Self {
+        assert!(
+            ratio.is_finite() && ratio.is_sign_positive(),
+            "invalid expired ratio"
+        );
+        Self(ratio.min(1.0))
+    }
+}
+
+impl<C: Compressor + Clone> GcStrategy<C> for ExpiredRatioStrategy {
+    fn pick(&self, value_log: &ValueLog<C>) -> Vec<SegmentId> {
+        let now = unix_timestamp_millis();
+
+        value_log
+            .manifest
+            .segments
+            .read()
+            .expect("lock is poisoned")
+            .values()
+            .filter(|x| !x.is_stale())
+            .filter(|x| {
+                // NOTE: The threshold is inclusive, so a ratio of 1.0 picks fully expired segments
+                let ratio = x.expired_ratio(now);
+                ratio > 0.0 && ratio >= self.0
+            })
+            .map(|x| x.id)
+            .collect::<Vec<_>>()
+    }
+}
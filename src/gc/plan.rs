@@ -0,0 +1,58 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::id::SegmentId;
+
+/// Projected effect of running a GC strategy, see [`ValueLog::plan_gc`](crate::ValueLog::plan_gc)
+///
+/// The projection is based on the current GC statistics, so it is only
+/// as accurate as the statistics.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[allow(clippy::module_name_repetitions)]
+pub struct GcPlan {
+    /// Segments the strategy picked
+    pub segment_ids: Vec<SegmentId>,
+
+    /// Amount of bytes (on disk) that would be read from the picked segments
+    pub bytes_to_read: u64,
+
+    /// Estimated amount of live bytes (uncompressed) that would be rewritten
+    pub live_bytes_to_rewrite: u64,
+
+    /// Estimated amount of stale bytes (uncompressed) that would be freed
+    pub stale_bytes_to_free: u64,
+
+    /// Current space amplification
+    pub space_amp_before: f32,
+
+    /// Projected space amplification after running GC
+    /// and dropping the stale segments
+    pub space_amp_after: f32,
+}
+
+impl std::fmt::Display for GcPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "--- GC plan ---")?;
+        writeln!(f, "Segments     : {:?}", self.segment_ids)?;
+        writeln!(f, "Bytes to read: {}", self.bytes_to_read)?;
+        writeln!(f, "Live bytes   : {}", self.live_bytes_to_rewrite)?;
+        writeln!(f, "Stale bytes  : {}", self.stale_bytes_to_free)?;
+        writeln!(
+            f,
+            "Space amp    : {} -> {}",
+            self.space_amp_before, self.space_amp_after
+        )?;
+        writeln!(f, "--- GC plan done ---")?;
+        Ok(())
+    }
+}
+
+impl GcPlan {
+    /// Returns `true` if the strategy would not do anything.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.segment_ids.is_empty()
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{id::SegmentId, Compressor, IndexReader, IndexWriter, ValueLog};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Limits the work done by [`ValueLog::rollover_bounded`]
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct RolloverBudget {
+    max_bytes: Option<u64>,
+    max_segments: Option<usize>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl RolloverBudget {
+    /// Sets the maximum amount of bytes (on disk) that are read from the old segments.
+    ///
+    /// At least one segment is always rewritten, even if it is
+    /// larger than the budget.
+    ///
+    /// Default = unlimited
+    #[must_use]
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum amount of segments that are rewritten.
+    ///
+    /// Default = unlimited
+    #[must_use]
+    pub fn max_segments(mut self, count: usize) -> Self {
+        self.max_segments = Some(count);
+        self
+    }
+
+    /// Sets a cancellation token.
+    ///
+    /// The token is checked before each segment is rewritten.
+    /// When it is set to `true`, the rollover stops after the segment
+    /// that is currently being rewritten.
+    ///
+    /// Default = none
+    #[must_use]
+    pub fn cancel_token(mut self, token: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|x| x.load(Ordering::Acquire))
+    }
+
+    fn allows(&self, progress: &RolloverProgress, segment_bytes: u64) -> bool {
+        if self.is_cancelled() {
+            return false;
+        }
+
+        if progress.rewritten_segments.is_empty() {
+            return true;
+        }
+
+        let within_bytes = self
+            .max_bytes
+            .map_or(true, |x| progress.bytes_read + segment_bytes <= x);
+
+        let within_segments = self
+            .max_segments
+            .map_or(true, |x| progress.rewritten_segments.len() < x);
+
+        within_bytes && within_segments
+    }
+}
+
+/// Progress of [`ValueLog::rollover_bounded`]
+#[derive(Debug, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct RolloverProgress {
+    /// Segments that were rewritten (and are now stale)
+    pub rewritten_segments: Vec<SegmentId>,
+
+    /// Segments that still need to be rewritten
+    ///
+    /// Pass these to the next call to resume the rollover.
+    pub remaining_segments: Vec<SegmentId>,
+
+    /// Amount of bytes (on disk) read from the rewritten segments
+    pub bytes_read: u64,
+
+    /// Amount of bytes (on disk) written into new segments
+    pub bytes_written: u64,
+
+    /// Amount of disk space that is freed once the rewritten
+    /// segments are dropped (see [`ValueLog::drop_stale_segments`])
+    pub bytes_freed: u64,
+}
+
+impl RolloverProgress {
+    /// Returns `true` if all segments were rewritten.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.remaining_segments.is_empty()
+    }
+}
+
+impl<C: Compressor + Clone> ValueLog<C> {
+    /// Rewrites segments like [`ValueLog::rollover`], but only as many as the
+    /// given budget allows, so large rollovers can be spread out over multiple calls.
+    ///
+    /// Segments are rewritten one at a time: the new segment is registered,
+    /// the index writer is finished and the old segment is marked as stale
+    /// before moving on to the next one. So, the index writer factory
+    /// is called once per segment.
+    ///
+    /// Segments that no longer exist are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    /// Segments that were rewritten before the error stay rewritten.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    pub fn rollover_bounded<R: IndexReader, W: IndexWriter>(
+        &self,
+        ids: &[SegmentId],
+        budget: &RolloverBudget,
+        index_reader: &R,
+        mut index_writer: impl FnMut() -> W,
+    ) -> crate::Result<RolloverProgress> {
+        let mut progress = RolloverProgress::default();
+
+        for (idx, &id) in ids.iter().enumerate() {
+            // IMPORTANT: Only allow 1 rollover or GC at any given time
+            let _guard = self.rollover_guard.lock().expect("lock is poisoned");
+
+            let Some(segment) = self.manifest.get_segment(id) else {
+                log::trace!("Blob file #{id} does not exist anymore, skipping");
+                continue;
+            };
+
+            if !budget.allows(&progress, segment.meta.compressed_bytes) {
+                progress.remaining_segments = ids.iter().skip(idx).copied().collect();
+                break;
+            }
+
+            let bytes_written = self.rewrite_segments(&[id], &[], index_reader, index_writer())?;
+
+            progress.rewritten_segments.push(id);
+            progress.bytes_read += segment.meta.compressed_bytes;
+            progress.bytes_written += bytes_written;
+            progress.bytes_freed += segment.meta.compressed_bytes.saturating_sub(bytes_written);
+        }
+
+        log::debug!(
+            "Bounded rollover rewrote {} segments, {} remaining",
+            progress.rewritten_segments.len(),
+            progress.remaining_segments.len(),
+        );
+
+        Ok(progress)
+    }
+}
@@ -0,0 +1,610 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    value_log::ValueLogInner, Compressor, GcStrategy, IndexReader, IndexWriter, ValueHandle,
+    ValueLog,
+};
+use std::{
+    sync::{Arc, Condvar, Mutex, Weak},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+type ScanIter = Box<dyn Iterator<Item = std::io::Result<(ValueHandle, u32)>>>;
+type ScanFactory = Box<dyn Fn() -> ScanIter + Send>;
+type RunCallback = Box<dyn Fn(&crate::Result<()>) + Send>;
+
+/// Configuration of a [`GcWorker`]
+#[allow(clippy::module_name_repetitions)]
+pub struct GcWorkerConfig {
+    /// Run GC at least this often
+    pub(crate) interval: Option<Duration>,
+
+    /// Run GC if the space amplification reaches this threshold
+    pub(crate) space_amp_trigger: Option<f32>,
+
+    /// How often the worker checks if it needs to run
+    pub(crate) poll_interval: Duration,
+
+    /// Rewrite I/O limit
+    pub(crate) max_bytes_per_second: Option<u64>,
+
+    /// Drop stale segments after rewriting
+    pub(crate) drop_stale_segments: bool,
+
+    /// Index scan used to reconcile GC stats before every run
+    pub(crate) scan: Option<ScanFactory>,
+
+    /// Called after every run
+    pub(crate) on_run: Option<RunCallback>,
+}
+
+impl Default for GcWorkerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Some(Duration::from_secs(60)),
+            space_amp_trigger: None,
+            poll_interval: Duration::from_secs(1),
+            max_bytes_per_second: None,
+            drop_stale_segments: false,
+            scan: None,
+            on_run: None,
+        }
+    }
+}
+
+impl GcWorkerConfig {
+    /// Sets the interval GC is run in.
+    ///
+    /// `None` disables periodic runs.
+    ///
+    /// Default = 60 seconds
+    #[must_use]
+    pub fn interval(mut self, interval: Option<Duration>) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Runs GC whenever the space amplification reaches the given threshold.
+    ///
+    /// Default = none
+    ///
+    /// # Panics
+    ///
+    /// Panics if the threshold is < 1.0.
+    #[must_use]
+    pub fn space_amp_trigger(mut self, threshold: f32) -> Self {
+        assert!(threshold >= 1.0, "invalid space amp threshold");
+        self.space_amp_trigger = Some(threshold);
+        self
+    }
+
+    /// Sets how often the worker checks if it needs to run.
+    ///
+    /// Default = 1 second
+    #[must_use]
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Limits how many bytes per second are rewritten.
+    ///
+    /// Segments are rewritten in batches of up to one second worth of bytes,
+    /// and the worker is throttled by the amount of bytes it actually wrote.
+    /// A single large segment may temporarily exceed the limit.
+    ///
+    /// Default = unlimited
+    #[must_use]
+    pub fn max_bytes_per_second(mut self, bytes: u64) -> Self {
+        self.max_bytes_per_second = Some(bytes);
+        self
+    }
+
+    /// Sets whether stale segments are dropped after rewriting segments.
+    ///
+    /// By default, the index decides when it is safe to drop segments
+    /// (readers may still hold value handles that point into them),
+    /// and calls [`ValueLog::drop_stale_segments`] manually.
+    ///
+    /// Only enable this if no reader can hold on to old value handles.
+    ///
+    /// Default = false
+    #[must_use]
+    pub fn drop_stale_segments(mut self, enabled: bool) -> Self {
+        self.drop_stale_segments = enabled;
+        self
+    }
+
+    /// Scans the index before every run to reconcile the GC statistics,
+    /// see [`ValueLog::scan_for_stats`].
+    ///
+    /// Not needed if staleness is reported using [`ValueLog::mark_blobs_stale`].
+    ///
+    /// Default = none
+    #[must_use]
+    pub fn scan_for_stats<
+        I: Iterator<Item = std::io::Result<(ValueHandle, u32)>> + 'static,
+        F: Fn() -> I + Send + 'static,
+    >(
+        mut self,
+        f: F,
+    ) -> Self {
+        self.scan = Some(Box::new(move || Box::new(f())));
+        self
+    }
+
+    /// Calls the given function after every run, with the result of the run.
+    ///
+    /// Default = none
+    #[must_use]
+    pub fn on_run<F: Fn(&crate::Result<()>) + Send + 'static>(mut self, f: F) -> Self {
+        self.on_run = Some(Box::new(f));
+        self
+    }
+}
+
+/// Simple token bucket that allows bursts of up to one second worth of bytes
+struct RateLimiter {
+    bytes_per_second: u64,
+    available: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            available: bytes_per_second as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes the given amount of bytes from the bucket, returning
+    /// how long to wait before the bytes can be written.
+    #[allow(clippy::cast_precision_loss)]
+    fn acquire(&mut self, bytes: u64) -> Duration {
+        let now = Instant::now();
+        let rate = self.bytes_per_second as f64;
+
+        self.available = now
+            .duration_since(self.last_refill)
+            .as_secs_f64()
+            .mul_add(rate, self.available)
+            .min(rate);
+        self.last_refill = now;
+
+        self.available -= bytes as f64;
+
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available / rate)
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    paused: bool,
+    shutdown: bool,
+    wake: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    signal: Condvar,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+// NOTE: Locks are only poisoned if a thread panicked while holding them
+#[allow(clippy::expect_used)]
+impl Shared {
+    /// Sleeps for the given duration, returns `false` if the
+    /// worker was paused or shut down in the meantime.
+    #[allow(clippy::significant_drop_tightening)]
+    fn sleep(&self, duration: Duration) -> bool {
+        let state = self.state.lock().expect("lock is poisoned");
+
+        let (state, _) = self
+            .signal
+            .wait_timeout_while(state, duration, |x| !x.paused && !x.shutdown)
+            .expect("lock is poisoned");
+
+        !state.paused && !state.shutdown
+    }
+
+    fn is_interrupted(&self) -> bool {
+        let state = self.state.lock().expect("lock is poisoned");
+        state.paused || state.shutdown
+    }
+
+    /// Waits until the next poll, returns `None` if the worker
+    /// was shut down, or else if it was woken up explicitly.
+    #[allow(clippy::significant_drop_tightening)]
+    fn wait_for_poll(&self, poll_interval: Duration) -> Option<bool> {
+        let state = self.state.lock().expect("lock is poisoned");
+
+        let (state, _) = self
+            .signal
+            .wait_timeout_while(state, poll_interval, |x| !x.shutdown && !x.wake)
+            .expect("lock is poisoned");
+
+        let mut state = self
+            .signal
+            .wait_while(state, |x| x.paused && !x.shutdown)
+            .expect("lock is poisoned");
+
+        if state.shutdown {
+            None
+        } else {
+            Some(std::mem::take(&mut state.wake))
+        }
+    }
+}
+
+/// Handle to a background garbage collection worker
+///
+/// The worker is owned by the value log, and is shut down
+/// when the value log is dropped.
+///
+/// See [`ValueLog::start_gc_worker`].
+#[derive(Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct GcWorker(Arc<Shared>);
+
+impl std::fmt::Debug for GcWorker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GcWorker")
+            .field("running", &self.is_running())
+            .field("paused", &self.is_paused())
+            .finish()
+    }
+}
+
+// NOTE: Locks are only poisoned if a thread panicked while holding them
+#[allow(clippy::expect_used)]
+impl GcWorker {
+    /// Pauses the worker.
+    ///
+    /// A running GC is interrupted after rewriting the current batch of segments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    pub fn pause(&self) {
+        self.0.state.lock().expect("lock is poisoned").paused = true;
+        self.0.signal.notify_all();
+    }
+
+    /// Resumes the worker after it was paused.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    pub fn resume(&self) {
+        self.0.state.lock().expect("lock is poisoned").paused = false;
+        self.0.signal.notify_all();
+    }
+
+    /// Returns `true` if the worker is paused.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.0.state.lock().expect("lock is poisoned").paused
+    }
+
+    /// Runs GC as soon as possible, regardless of the configured triggers.
+    ///
+    /// If the worker is paused, GC runs after it is resumed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    pub fn wake(&self) {
+        self.0.state.lock().expect("lock is poisoned").wake = true;
+        self.0.signal.notify_all();
+    }
+
+    /// Shuts down the worker, waiting for it to finish the current batch of segments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    pub fn shutdown(&self) {
+        self.0.state.lock().expect("lock is poisoned").shutdown = true;
+        self.0.signal.notify_all();
+
+        let thread = self.0.thread.lock().expect("lock is poisoned").take();
+
+        if let Some(thread) = thread {
+            // NOTE: The value log may be dropped by the worker itself
+            if thread.thread().id() == std::thread::current().id() {
+                return;
+            }
+
+            if thread.join().is_err() {
+                log::error!("GC worker panicked");
+            }
+        }
+    }
+
+    /// Returns `true` if the worker thread is running.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.0
+            .thread
+            .lock()
+            .expect("lock is poisoned")
+            .as_ref()
+            .is_some_and(|x| !x.is_finished())
+    }
+}
+
+struct Worker<C, S, R, W, FR, FW>
+where
+    C: Compressor + Clone,
+{
+    value_log: Weak<ValueLogInner<C>>,
+    config: GcWorkerConfig,
+    strategy: S,
+    index_reader: FR,
+    index_writer: FW,
+    rate_limiter: Option<RateLimiter>,
+    shared: Arc<Shared>,
+    _marker: std::marker::PhantomData<fn() -> (R, W)>,
+}
+
+impl<C, S, R, W, FR, FW> Worker<C, S, R, W, FR, FW>
+where
+    C: Compressor + Clone,
+    S: GcStrategy<C>,
+    R: IndexReader,
+    W: IndexWriter,
+    FR: Fn() -> R,
+    FW: Fn() -> W,
+{
+    fn value_log(&self) -> Option<ValueLog<C>> {
+        self.value_log.upgrade().map(ValueLog)
+    }
+
+    /// Returns the GC statistics the strategy picks segments by.
+    fn gc_stats(value_log: &ValueLog<C>) -> (u64, u64) {
+        (
+            value_log.manifest.total_bytes(),
+            value_log.manifest.stale_bytes(),
+        )
+    }
+
+    fn run(mut self) {
+        let mut last_run = Instant::now();
+
+        // NOTE: GC stats of the last run that picked nothing, as long as they
+        // do not change, the space amp trigger would just pick nothing again
+        let mut idle_stats = None;
+
+        while let Some(woken) = self.shared.wait_for_poll(self.config.poll_interval) {
+            let Some(value_log) = self.value_log() else {
+                break;
+            };
+
+            let is_due = woken
+                || self
+                    .config
+                    .interval
+                    .is_some_and(|x| last_run.elapsed() >= x)
+                || self.config.space_amp_trigger.is_some_and(|x| {
+                    value_log.space_amp() >= x && idle_stats != Some(Self::gc_stats(&value_log))
+                });
+
+            // NOTE: Do not keep the value log alive while sleeping
+            drop(value_log);
+
+            if !is_due {
+                continue;
+            }
+
+            last_run = Instant::now();
+
+            let result = match self.run_once() {
+                Ok(true) => {
+                    idle_stats = None;
+                    Ok(())
+                }
+                Ok(false) => {
+                    idle_stats = self.value_log().map(|x| Self::gc_stats(&x));
+                    Ok(())
+                }
+                Err(e) => {
+                    log::error!("GC worker failed: {e:?}");
+                    Err(e)
+                }
+            };
+
+            if let Some(on_run) = &self.config.on_run {
+                on_run(&result);
+            }
+        }
+
+        log::debug!("GC worker shut down");
+    }
+
+    /// Runs GC once, returns `false` if the strategy picked no segments.
+    fn run_once(&mut self) -> crate::Result<bool> {
+        let Some(value_log) = self.value_log() else {
+            return Ok(false);
+        };
+
+        if let Some(scan) = &self.config.scan {
+            value_log.scan_for_stats(scan())?;
+        }
+
+        let ids = self.strategy.pick(&value_log);
+        drop(value_log);
+
+        log::debug!("GC worker picked segments {ids:?}");
+
+        if ids.is_empty() {
+            return Ok(false);
+        }
+
+        // NOTE: With a rate limit, segments are rewritten in batches of up to
+        // one second worth of bytes, so the worker can be throttled in between
+        let max_batch_bytes = self.rate_limiter.as_ref().map(|x| x.bytes_per_second);
+
+        let mut ids = ids.into_iter().peekable();
+
+        while ids.peek().is_some() {
+            if self.shared.is_interrupted() {
+                log::debug!("GC worker interrupted");
+                return Ok(true);
+            }
+
+            let Some(value_log) = self.value_log() else {
+                return Ok(true);
+            };
+
+            let mut batch = vec![];
+            let mut batch_bytes = 0;
+
+            while let Some(&id) = ids.peek() {
+                let Some(segment) = value_log.manifest.get_segment(id) else {
+                    ids.next();
+                    continue;
+                };
+
+                let segment_bytes = segment.meta.compressed_bytes;
+
+                if !batch.is_empty()
+                    && max_batch_bytes.is_some_and(|max| batch_bytes + segment_bytes > max)
+                {
+                    break;
+                }
+
+                batch.push(id);
+                batch_bytes += segment_bytes;
+                ids.next();
+            }
+
+            let bytes_written = value_log.rollover_counting_writes(
+                &batch,
+                &(self.index_reader)(),
+                (self.index_writer)(),
+            )?;
+            drop(value_log);
+
+            if let Some(rate_limiter) = &mut self.rate_limiter {
+                let delay = rate_limiter.acquire(bytes_written);
+
+                if !delay.is_zero() {
+                    log::trace!("GC worker throttled for {delay:?}");
+
+                    if !self.shared.sleep(delay) {
+                        log::debug!("GC worker interrupted");
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+
+        if self.config.drop_stale_segments {
+            if let Some(value_log) = self.value_log() {
+                value_log.drop_stale_segments()?;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl<C: Compressor + Clone + Send + Sync + 'static> ValueLog<C> {
+    /// Starts a background worker that runs garbage collection using the given strategy.
+    ///
+    /// GC is run periodically, or when the space amplification reaches a threshold,
+    /// as configured. Because rewriting segments changes value handles, the worker
+    /// needs to be given factories that create an index reader and index writer.
+    ///
+    /// If a worker is already running, it is shut down first.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker thread could not be spawned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used)]
+    pub fn start_gc_worker<S, R, W, FR, FW>(
+        &self,
+        config: GcWorkerConfig,
+        strategy: S,
+        index_reader: FR,
+        index_writer: FW,
+    ) -> crate::Result<GcWorker>
+    where
+        S: GcStrategy<C> + Send + 'static,
+        R: IndexReader + 'static,
+        W: IndexWriter + 'static,
+        FR: Fn() -> R + Send + 'static,
+        FW: Fn() -> W + Send + 'static,
+    {
+        let mut lock = self.gc_worker.lock().expect("lock is poisoned");
+
+        if let Some(worker) = lock.take() {
+            worker.shutdown();
+        }
+
+        let shared = Arc::new(Shared::default());
+
+        let worker = Worker {
+            value_log: Arc::downgrade(&self.0),
+            rate_limiter: config.max_bytes_per_second.map(RateLimiter::new),
+            config,
+            strategy,
+            index_reader,
+            index_writer,
+            shared: shared.clone(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let thread = std::thread::Builder::new()
+            .name("value-log-gc".into())
+            .spawn(move || worker.run())?;
+
+        *shared.thread.lock().expect("lock is poisoned") = Some(thread);
+
+        let handle = GcWorker(shared);
+        *lock = Some(handle.clone());
+        drop(lock);
+
+        Ok(handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn rate_limiter_burst() {
+        let mut limiter = RateLimiter::new(1_000);
+
+        assert_eq!(Duration::ZERO, limiter.acquire(1_000));
+
+        let delay = limiter.acquire(500);
+        assert!(delay > Duration::from_millis(400));
+        assert!(delay <= Duration::from_millis(500));
+    }
+}
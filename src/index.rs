@@ -18,6 +18,21 @@ pub trait Reader {
     ///
     /// Will return `Err` if an IO error occurs.
     fn get(&self, key: &[u8]) -> std::io::Result<Option<ValueHandle>>;
+
+    /// Returns the value handles for the given keys, in the same order.
+    ///
+    /// During garbage collection, keys are passed in the merged order of the
+    /// rewritten segments (so, ascending if the segments were written in key order),
+    /// which allows implementations to perform a sorted multi-get.
+    ///
+    /// The default implementation calls [`Reader::get`] for every key.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn get_many(&self, keys: &[&[u8]]) -> std::io::Result<Vec<Option<ValueHandle>>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
 }
 
 /// Trait that allows writing into an external index
@@ -38,6 +53,32 @@ pub trait Writer {
         size: u32,
     ) -> std::io::Result<()>;
 
+    /// Inserts a value handle into the index write batch, but only if the key
+    /// still points to `expected`.
+    ///
+    /// This is used by garbage collection to relocate blobs: if the key was
+    /// overwritten after the value log read its handle, the insert must be
+    /// dropped, otherwise the newer value would be lost.
+    /// The comparison needs to be atomic with applying the write
+    /// (so, at the latest when `finish` is called).
+    ///
+    /// The default implementation inserts unconditionally,
+    /// so indexes that are written concurrently to GC should override it.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn insert_indirect_if(
+        &mut self,
+        key: &[u8],
+        expected: &ValueHandle,
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> std::io::Result<()> {
+        let _ = expected;
+        self.insert_indirect(key, vhandle, size)
+    }
+
     /// Finishes the write batch.
     ///
     /// # Errors
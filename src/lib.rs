@@ -100,10 +100,16 @@
 #![allow(clippy::missing_const_for_fn)]
 #![warn(clippy::multiple_crate_versions)]
 
+#[cfg(feature = "async")]
+mod async_value_log;
+
 mod blob_cache;
+mod blob_reader;
 mod coding;
 mod compression;
 mod config;
+mod descriptor_table;
+mod encryption;
 mod error;
 mod gc;
 mod handle;
@@ -113,12 +119,14 @@ mod key_range;
 mod manifest;
 mod mock;
 mod path;
+mod positional_reader;
 mod slice;
 
 #[doc(hidden)]
 pub mod scanner;
 
 mod segment;
+mod time;
 mod value;
 mod value_log;
 mod version;
@@ -127,11 +135,20 @@ pub(crate) type HashMap<K, V> = std::collections::HashMap<K, V, xxhash_rust::xxh
 
 pub use {
     blob_cache::BlobCache,
-    compression::Compressor,
-    config::Config,
+    blob_reader::BlobReader,
+    compression::{CompressionType, Compressor},
+    config::{ChecksumVerification, Config},
+    descriptor_table::DescriptorTable,
+    encryption::Encryptor,
     error::{Error, Result},
+    gc::plan::GcPlan,
     gc::report::GcReport,
-    gc::{GcStrategy, SpaceAmpStrategy, StaleThresholdStrategy},
+    gc::rollover::{RolloverBudget, RolloverProgress},
+    gc::worker::{GcWorker, GcWorkerConfig},
+    gc::{
+        CostBenefitStrategy, ExpiredRatioStrategy, GcStrategy, SpaceAmpStrategy,
+        StaleThresholdStrategy,
+    },
     handle::ValueHandle,
     index::{Reader as IndexReader, Writer as IndexWriter},
     segment::multi_writer::MultiWriter as SegmentWriter,
@@ -141,11 +158,26 @@ pub use {
     version::Version,
 };
 
+#[cfg(feature = "lz4")]
+pub use compression::Lz4Compressor;
+
+#[cfg(feature = "zstd")]
+pub use compression::ZstdCompressor;
+
+#[cfg(feature = "aes-gcm")]
+pub use encryption::AesGcmEncryptor;
+
+#[cfg(feature = "async")]
+pub use async_value_log::{
+    index::{AsyncReader as AsyncIndexReader, AsyncWriter as AsyncIndexWriter, BoxFuture},
+    AsyncValueLog,
+};
+
 #[doc(hidden)]
 pub use segment::{reader::Reader as SegmentReader, Segment};
 
 #[doc(hidden)]
-pub use mock::{MockIndex, MockIndexWriter};
+pub use mock::{MockBatchIndexWriter, MockIndex, MockIndexWriter};
 
 #[doc(hidden)]
 pub use key_range::KeyRange;
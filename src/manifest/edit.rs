@@ -0,0 +1,74 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    coding::{Decode, DecodeError, Encode, EncodeError},
+    id::SegmentId,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+
+/// A change to the segment manifest
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Edit {
+    /// Registers a new segment
+    Add(SegmentId),
+
+    /// Removes a segment
+    Remove(SegmentId),
+
+    /// Updates a segment's GC statistics
+    GcStats {
+        /// Segment ID
+        segment_id: SegmentId,
+
+        /// Amount of stale blobs
+        stale_items: u64,
+
+        /// Amount of stale bytes (uncompressed)
+        stale_bytes: u64,
+    },
+}
+
+impl Encode for Edit {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        match self {
+            Self::Add(id) => {
+                writer.write_u8(0)?;
+                writer.write_u64::<BigEndian>(*id)?;
+            }
+            Self::Remove(id) => {
+                writer.write_u8(1)?;
+                writer.write_u64::<BigEndian>(*id)?;
+            }
+            Self::GcStats {
+                segment_id,
+                stale_items,
+                stale_bytes,
+            } => {
+                writer.write_u8(2)?;
+                writer.write_u64::<BigEndian>(*segment_id)?;
+                writer.write_u64::<BigEndian>(*stale_items)?;
+                writer.write_u64::<BigEndian>(*stale_bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for Edit {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            0 => Ok(Self::Add(reader.read_u64::<BigEndian>()?)),
+            1 => Ok(Self::Remove(reader.read_u64::<BigEndian>()?)),
+            2 => Ok(Self::GcStats {
+                segment_id: reader.read_u64::<BigEndian>()?,
+                stale_items: reader.read_u64::<BigEndian>()?,
+                stale_bytes: reader.read_u64::<BigEndian>()?,
+            }),
+            tag => Err(DecodeError::InvalidTag(("ManifestEdit", tag))),
+        }
+    }
+}
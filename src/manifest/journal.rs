@@ -0,0 +1,178 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::edit::Edit;
+use crate::{
+    coding::{Decode, DecodeError, Encode},
+    version::Version,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::Cursor;
+
+/// Manifest header magic, followed by the format version
+pub const MANIFEST_HEADER_MAGIC: &[u8] = b"VLOGMAN";
+
+/// Record length + payload checksum + header checksum
+const RECORD_HEADER_SIZE: usize =
+    std::mem::size_of::<u32>() + std::mem::size_of::<u64>() + std::mem::size_of::<u64>();
+
+/// Size of the part of the record header that is covered by the header checksum
+const RECORD_HEADER_CHECKED_SIZE: usize = RECORD_HEADER_SIZE - std::mem::size_of::<u64>();
+
+/// Size of a legacy manifest entry (segment count or segment ID)
+const LEGACY_ENTRY_SIZE: usize = std::mem::size_of::<u64>();
+
+/// Contents of a manifest log
+pub struct LogContent {
+    /// All edits, in order
+    pub edits: Vec<Edit>,
+
+    /// `true` if the last record was only partially written
+    pub is_torn: bool,
+}
+
+/// Encodes the manifest file header.
+pub fn encode_header() -> Vec<u8> {
+    let mut bytes = MANIFEST_HEADER_MAGIC.to_vec();
+    bytes.push(Version::V2.into());
+    bytes
+}
+
+/// Encodes a batch of edits into a checksummed record.
+///
+/// Records are formatted like this:
+///
+/// \[payload length; 4 bytes] \[xxh3 of payload; 8 bytes]
+/// \[xxh3 of length and payload checksum; 8 bytes] \[edits]
+///
+/// The header checksum protects the payload length, so a corrupted length
+/// cannot be mistaken for a partially written record.
+pub fn encode_record(edits: &[Edit]) -> crate::Result<Vec<u8>> {
+    let mut payload = vec![];
+
+    for edit in edits {
+        edit.encode_into(&mut payload)?;
+    }
+
+    let checksum = xxhash_rust::xxh3::xxh3_64(&payload);
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + payload.len());
+
+    // NOTE: Truncation is okay, a record is far smaller than 4 GiB
+    #[allow(clippy::cast_possible_truncation)]
+    record.write_u32::<BigEndian>(payload.len() as u32)?;
+
+    record.write_u64::<BigEndian>(checksum)?;
+
+    let header_checksum = xxhash_rust::xxh3::xxh3_64(&record);
+    record.write_u64::<BigEndian>(header_checksum)?;
+
+    record.extend_from_slice(&payload);
+
+    Ok(record)
+}
+
+/// Parses a manifest log.
+///
+/// Returns `None` if the file is not a manifest log, but a legacy manifest.
+///
+/// A partially written record at the end of the log is ignored,
+/// because it was never acknowledged.
+///
+/// # Errors
+///
+/// Will return `Err` if the header or a record is corrupted.
+pub fn read_log(bytes: &[u8]) -> crate::Result<Option<LogContent>> {
+    let Some(rest) = bytes.strip_prefix(MANIFEST_HEADER_MAGIC) else {
+        if is_legacy_manifest(bytes) {
+            return Ok(None);
+        }
+
+        return Err(crate::Error::Decode(DecodeError::InvalidHeader("Manifest")));
+    };
+
+    let Some((&version, mut rest)) = rest.split_first() else {
+        return Err(crate::Error::Decode(DecodeError::InvalidHeader("Manifest")));
+    };
+
+    if Version::try_from(version) != Ok(Version::V2) {
+        return Err(crate::Error::Decode(DecodeError::InvalidTag((
+            "ManifestVersion",
+            version,
+        ))));
+    }
+
+    let mut offset = (MANIFEST_HEADER_MAGIC.len() + 1) as u64;
+    let mut edits = vec![];
+    let mut is_torn = false;
+
+    while !rest.is_empty() {
+        // NOTE: Only the last record can be cut off, because records are only appended
+        let Some(header) = rest.get(..RECORD_HEADER_SIZE) else {
+            is_torn = true;
+            break;
+        };
+
+        let mut reader = Cursor::new(header);
+        let payload_len = reader.read_u32::<BigEndian>()? as usize;
+        let checksum = reader.read_u64::<BigEndian>()?;
+        let header_checksum = reader.read_u64::<BigEndian>()?;
+
+        #[allow(clippy::indexing_slicing)]
+        if xxhash_rust::xxh3::xxh3_64(&header[..RECORD_HEADER_CHECKED_SIZE]) != header_checksum {
+            return Err(crate::Error::Decode(DecodeError::InvalidChecksum((
+                "ManifestRecordHeader",
+                offset,
+            ))));
+        }
+
+        // NOTE: The length is intact, so the file really ends early
+        let Some(payload) = rest.get(RECORD_HEADER_SIZE..(RECORD_HEADER_SIZE + payload_len)) else {
+            is_torn = true;
+            break;
+        };
+
+        if xxhash_rust::xxh3::xxh3_64(payload) != checksum {
+            return Err(crate::Error::Decode(DecodeError::InvalidChecksum((
+                "ManifestRecord",
+                offset,
+            ))));
+        }
+
+        let mut cursor = Cursor::new(payload);
+
+        while cursor.position() < payload.len() as u64 {
+            edits.push(Edit::decode_from(&mut cursor)?);
+        }
+
+        let record_size = RECORD_HEADER_SIZE + payload_len;
+        rest = rest.get(record_size..).unwrap_or_default();
+        offset += record_size as u64;
+    }
+
+    if is_torn {
+        log::warn!("Ignoring partially written manifest record at offset {offset}");
+    }
+
+    Ok(Some(LogContent { edits, is_torn }))
+}
+
+/// Returns `true` if the bytes are a legacy manifest.
+///
+/// Legacy manifests have no header, and consist of the segment count,
+/// followed by the segment IDs.
+fn is_legacy_manifest(bytes: &[u8]) -> bool {
+    let Some(count) = bytes
+        .get(..LEGACY_ENTRY_SIZE)
+        .and_then(|x| x.try_into().ok())
+        .map(u64::from_be_bytes)
+    else {
+        return false;
+    };
+
+    count
+        .checked_add(1)
+        .and_then(|x| x.checked_mul(LEGACY_ENTRY_SIZE as u64))
+        .is_some_and(|x| x == bytes.len() as u64)
+}
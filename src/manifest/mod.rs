@@ -2,27 +2,39 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+mod edit;
+mod journal;
+
 use crate::{
     id::SegmentId,
-    key_range::KeyRange,
-    segment::{gc_stats::GcStats, meta::Metadata, trailer::SegmentFileTrailer},
+    segment::{gc_stats::GcStats, trailer::SegmentFileTrailer},
+    time::to_unix_millis,
+    version::Version,
     Compressor, HashMap, Segment, SegmentWriter as MultiWriter,
 };
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ReadBytesExt};
+use edit::Edit;
 use std::{
+    collections::BTreeMap,
+    fs::File,
     io::{Cursor, Write},
-    marker::PhantomData,
     path::{Path, PathBuf},
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
 };
 
 pub const VLOG_MARKER: &str = ".vlog";
 pub const SEGMENTS_FOLDER: &str = "segments";
 const MANIFEST_FILE: &str = "vlog_manifest";
 
+/// Minimum amount of edits in the manifest log before it is compacted
+const COMPACTION_MIN_EDITS: usize = 128;
+
 /// Atomically rewrites a file
-fn rewrite_atomic<P: AsRef<Path>>(path: P, content: &[u8]) -> std::io::Result<()> {
+pub fn rewrite_atomic<P: AsRef<Path>>(path: P, content: &[u8]) -> std::io::Result<()> {
     let path = path.as_ref();
+
+    // NOTE: The manifest is always stored inside the value log folder
+    #[allow(clippy::expect_used)]
     let folder = path.parent().expect("should have a parent");
 
     let mut temp_file = tempfile::NamedTempFile::new_in(folder)?;
@@ -40,10 +52,29 @@ fn rewrite_atomic<P: AsRef<Path>>(path: P, content: &[u8]) -> std::io::Result<()
     Ok(())
 }
 
+/// Recovered segment IDs, with their stale item & byte counts
+type RecoveredSegments = BTreeMap<SegmentId, (u64, u64)>;
+
+/// State of the manifest log file
+#[derive(Default)]
+struct ManifestLog {
+    /// File handle for appending records, opened on first write
+    file: Option<File>,
+
+    /// Amount of edits in the log
+    edit_count: usize,
+
+    /// If `true`, the log needs to be rewritten before appending to it
+    ///
+    /// This is the case for legacy manifests and logs with a torn tail.
+    needs_rewrite: bool,
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub struct SegmentManifestInner<C: Compressor + Clone> {
     path: PathBuf,
     pub segments: RwLock<HashMap<SegmentId, Arc<Segment<C>>>>,
+    log: Mutex<ManifestLog>,
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -67,6 +98,8 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
             let dirent = dirent?;
 
             if dirent.file_type()?.is_dir() {
+                // NOTE: Only segment folders are created in the segments folder
+                #[allow(clippy::expect_used)]
                 let segment_id = dirent
                     .file_name()
                     .to_str()
@@ -84,34 +117,82 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
         Ok(())
     }
 
-    /// Parses segment IDs from manifest file
-    fn load_ids_from_disk<P: AsRef<Path>>(path: P) -> crate::Result<Vec<SegmentId>> {
+    /// Parses segment IDs and their GC statistics from manifest file
+    ///
+    /// Legacy manifests (without a header) are supported, but need to be rewritten.
+    fn load_ids_from_disk<P: AsRef<Path>>(
+        path: P,
+    ) -> crate::Result<(RecoveredSegments, ManifestLog)> {
         let path = path.as_ref();
         log::debug!("Loading manifest from {}", path.display());
 
         let bytes = std::fs::read(path)?;
 
-        let mut ids = vec![];
+        if let Some(content) = journal::read_log(&bytes)? {
+            let mut segments = BTreeMap::new();
+
+            for edit in &content.edits {
+                match edit {
+                    Edit::Add(id) => {
+                        segments.insert(*id, (0, 0));
+                    }
+                    Edit::Remove(id) => {
+                        segments.remove(id);
+                    }
+                    Edit::GcStats {
+                        segment_id,
+                        stale_items,
+                        stale_bytes,
+                    } => {
+                        if let Some(stats) = segments.get_mut(segment_id) {
+                            *stats = (*stale_items, *stale_bytes);
+                        }
+                    }
+                }
+            }
+
+            return Ok((
+                segments,
+                ManifestLog {
+                    file: None,
+                    edit_count: content.edits.len(),
+                    needs_rewrite: content.is_torn,
+                },
+            ));
+        }
+
+        log::debug!("Loading legacy manifest");
+
+        let mut segments = BTreeMap::new();
 
         let mut cursor = Cursor::new(bytes);
 
         let cnt = cursor.read_u64::<BigEndian>()?;
 
         for _ in 0..cnt {
-            ids.push(cursor.read_u64::<BigEndian>()?);
+            segments.insert(cursor.read_u64::<BigEndian>()?, (0, 0));
         }
 
-        Ok(ids)
+        Ok((
+            segments,
+            ManifestLog {
+                needs_rewrite: true,
+                ..Default::default()
+            },
+        ))
     }
 
     /// Recovers a value log from disk
-    pub(crate) fn recover<P: AsRef<Path>>(folder: P) -> crate::Result<Self> {
+    ///
+    /// The given compressor is used to decompress the recovered segments.
+    pub(crate) fn recover<P: AsRef<Path>>(folder: P, compressor: &C) -> crate::Result<Self> {
         let folder = folder.as_ref();
         let manifest_path = folder.join(MANIFEST_FILE);
 
-        log::info!("Recovering vLog at {folder:?}");
+        log::info!("Recovering vLog at {}", folder.display());
 
-        let ids = Self::load_ids_from_disk(&manifest_path)?;
+        let (recovered, manifest_log) = Self::load_ids_from_disk(&manifest_path)?;
+        let ids = recovered.keys().copied().collect::<Vec<_>>();
         let cnt = ids.len();
 
         let progress_mod = match cnt {
@@ -120,7 +201,7 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
             _ => 100,
         };
 
-        log::debug!("Recovering {cnt} vLog segments from {folder:?}");
+        log::debug!("Recovering {cnt} vLog segments from {}", folder.display());
 
         let segments_folder = folder.join(SEGMENTS_FOLDER);
         Self::remove_unfinished_segments(&segments_folder, &ids)?;
@@ -129,11 +210,22 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
             let mut map =
                 HashMap::with_capacity_and_hasher(100, xxhash_rust::xxh3::Xxh3Builder::new());
 
-            for (idx, &id) in ids.iter().enumerate() {
+            for (idx, (&id, &(stale_items, stale_bytes))) in recovered.iter().enumerate() {
                 log::trace!("Recovering segment #{id:?}");
 
                 let path = segments_folder.join(id.to_string());
-                let trailer = SegmentFileTrailer::from_file(&path)?;
+                let mut trailer = SegmentFileTrailer::from_file(&path)?;
+
+                if trailer.metadata.version == Version::V1 {
+                    trailer.metadata.created_at =
+                        to_unix_millis(std::fs::metadata(&path)?.modified()?);
+                }
+
+                let decompressor = Segment::resolve_decompressor(&trailer.metadata, compressor)?;
+
+                let gc_stats = GcStats::default();
+                gc_stats.set_stale_items(stale_items);
+                gc_stats.set_stale_bytes(stale_bytes);
 
                 map.insert(
                     id,
@@ -141,8 +233,8 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
                         id,
                         path,
                         meta: trailer.metadata,
-                        gc_stats: GcStats::default(),
-                        _phantom: PhantomData,
+                        gc_stats,
+                        decompressor,
                     }),
                 );
 
@@ -157,6 +249,7 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
         Ok(Self(Arc::new(SegmentManifestInner {
             path: manifest_path,
             segments: RwLock::new(segments),
+            log: Mutex::new(manifest_log),
         })))
     }
 
@@ -166,8 +259,9 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
         let m = Self(Arc::new(SegmentManifestInner {
             path,
             segments: RwLock::new(HashMap::default()),
+            log: Mutex::default(),
         }));
-        Self::write_to_disk(&m.path, &[])?;
+        Self::write_to_disk(&m.path, &HashMap::default())?;
 
         Ok(m)
     }
@@ -177,6 +271,8 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
         &self,
         f: F,
     ) -> crate::Result<()> {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         let mut prev_segments = self.segments.write().expect("lock is poisoned");
 
         // NOTE: Create a copy of the levels we can operate on
@@ -187,16 +283,26 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
 
         f(&mut working_copy);
 
-        let ids = working_copy.keys().copied().collect::<Vec<_>>();
-
-        Self::write_to_disk(&self.path, &ids)?;
+        let edits = prev_segments
+            .keys()
+            .filter(|id| !working_copy.contains_key(id))
+            .map(|&id| Edit::Remove(id))
+            .chain(
+                working_copy
+                    .keys()
+                    .filter(|id| !prev_segments.contains_key(id))
+                    .map(|&id| Edit::Add(id)),
+            )
+            .collect::<Vec<_>>();
+
+        self.append_to_disk(&edits, &working_copy)?;
         *prev_segments = working_copy;
 
         // NOTE: Lock needs to live until end of function because
         // writing to disk needs to be exclusive
         drop(prev_segments);
 
-        log::trace!("Swapped vLog segment list to: {ids:?}");
+        log::trace!("Swapped vLog segment list, applied edits: {edits:?}");
 
         Ok(())
     }
@@ -214,15 +320,15 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
             for writer in writers {
                 if writer.item_count == 0 {
                     log::debug!(
-                        "Writer at {:?} has written no data, deleting empty vLog segment file",
-                        writer.path
+                        "Writer at {} has written no data, deleting empty vLog segment file",
+                        writer.path.display(),
                     );
                     if let Err(e) = std::fs::remove_file(&writer.path) {
                         log::warn!(
-                            "Could not delete empty vLog segment file at {:?}: {e:?}",
-                            writer.path
+                            "Could not delete empty vLog segment file at {}: {e}",
+                            writer.path.display(),
                         );
-                    };
+                    }
                     continue;
                 }
 
@@ -232,28 +338,10 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
                     segment_id,
                     Arc::new(Segment {
                         id: segment_id,
-                        path: writer.path,
-                        meta: Metadata {
-                            item_count: writer.item_count,
-                            compressed_bytes: writer.written_blob_bytes,
-                            total_uncompressed_bytes: writer.uncompressed_bytes,
-
-                            // NOTE: We are checking for 0 items above
-                            // so first and last key need to exist
-                            #[allow(clippy::expect_used)]
-                            key_range: KeyRange::new((
-                                writer
-                                    .first_key
-                                    .clone()
-                                    .expect("should have written at least 1 item"),
-                                writer
-                                    .last_key
-                                    .clone()
-                                    .expect("should have written at least 1 item"),
-                            )),
-                        },
+                        meta: writer.metadata(),
+                        decompressor: writer.compression.clone(),
+                        path: writer.path.clone(),
                         gc_stats: GcStats::default(),
-                        _phantom: PhantomData,
                     }),
                 );
 
@@ -271,27 +359,125 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
         Ok(())
     }
 
-    fn write_to_disk<P: AsRef<Path>>(path: P, segment_ids: &[SegmentId]) -> crate::Result<()> {
+    /// Persists the current GC statistics of the given segments.
+    pub(crate) fn persist_gc_stats(&self, ids: &[SegmentId]) -> crate::Result<()> {
+        // NOTE: Read-locking is fine, because the log has its own lock
+        // but it prevents segments being swapped out concurrently
+        #[allow(clippy::significant_drop_tightening, clippy::expect_used)]
+        let segments = self.segments.read().expect("lock is poisoned");
+
+        let edits = ids
+            .iter()
+            .filter_map(|id| segments.get(id))
+            .map(|segment| Edit::GcStats {
+                segment_id: segment.id,
+                stale_items: segment.gc_stats.stale_items(),
+                stale_bytes: segment.gc_stats.stale_bytes(),
+            })
+            .collect::<Vec<_>>();
+
+        self.append_to_disk(&edits, &segments)
+    }
+
+    /// Appends edits to the manifest log, compacting it if it has grown too large
+    #[allow(clippy::significant_drop_tightening)]
+    fn append_to_disk(
+        &self,
+        edits: &[Edit],
+        segments: &HashMap<SegmentId, Arc<Segment<C>>>,
+    ) -> crate::Result<()> {
+        if edits.is_empty() {
+            return Ok(());
+        }
+
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
+        let mut manifest_log = self.log.lock().expect("lock is poisoned");
+        manifest_log.edit_count += edits.len();
+
+        if manifest_log.needs_rewrite
+            || manifest_log.edit_count > segments.len().max(COMPACTION_MIN_EDITS)
+        {
+            log::debug!(
+                "Compacting segment manifest ({} edits, {} segments)",
+                manifest_log.edit_count,
+                segments.len(),
+            );
+
+            // NOTE: The file is replaced, so the old handle is useless
+            manifest_log.file = None;
+
+            // NOTE: If writing fails, try to rewrite again next time
+            manifest_log.needs_rewrite = true;
+            manifest_log.edit_count = Self::write_to_disk(&self.path, segments)?;
+            manifest_log.needs_rewrite = false;
+
+            return Ok(());
+        }
+
+        log::trace!("Appending segment manifest edits: {edits:?}");
+
+        let record = journal::encode_record(edits)?;
+
+        let file = match &mut manifest_log.file {
+            Some(file) => file,
+            file => file.insert(std::fs::OpenOptions::new().append(true).open(&self.path)?),
+        };
+
+        // NOTE: If the record is only partially written, the next write
+        // needs to get rid of it by rewriting the log
+        let result = file.write_all(&record).and_then(|()| file.sync_data());
+
+        if result.is_err() {
+            manifest_log.needs_rewrite = true;
+        }
+
+        result.map_err(Into::into)
+    }
+
+    /// Writes a new manifest log that only contains the given segments
+    ///
+    /// Returns the amount of edits written.
+    fn write_to_disk<P: AsRef<Path>>(
+        path: P,
+        segments: &HashMap<SegmentId, Arc<Segment<C>>>,
+    ) -> crate::Result<usize> {
         let path = path.as_ref();
         log::trace!("Writing segment manifest to {}", path.display());
 
-        let mut bytes = Vec::new();
+        let mut edits = Vec::with_capacity(segments.len());
 
-        let cnt = segment_ids.len() as u64;
-        bytes.write_u64::<BigEndian>(cnt)?;
+        for segment in segments.values() {
+            edits.push(Edit::Add(segment.id));
 
-        for id in segment_ids {
-            bytes.write_u64::<BigEndian>(*id)?;
+            let stale_items = segment.gc_stats.stale_items();
+            let stale_bytes = segment.gc_stats.stale_bytes();
+
+            if stale_items > 0 || stale_bytes > 0 {
+                edits.push(Edit::GcStats {
+                    segment_id: segment.id,
+                    stale_items,
+                    stale_bytes,
+                });
+            }
+        }
+
+        let mut bytes = journal::encode_header();
+
+        if !edits.is_empty() {
+            bytes.extend(journal::encode_record(&edits)?);
         }
 
         rewrite_atomic(path, &bytes)?;
 
-        Ok(())
+        Ok(edits.len())
     }
 
     /// Gets a segment
     #[must_use]
     pub fn get_segment(&self, id: SegmentId) -> Option<Arc<Segment<C>>> {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         self.segments
             .read()
             .expect("lock is poisoned")
@@ -303,6 +489,8 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
     #[doc(hidden)]
     #[must_use]
     pub fn list_segment_ids(&self) -> Vec<SegmentId> {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         self.segments
             .read()
             .expect("lock is poisoned")
@@ -314,6 +502,8 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
     /// Lists all segments
     #[must_use]
     pub fn list_segments(&self) -> Vec<Arc<Segment<C>>> {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         self.segments
             .read()
             .expect("lock is poisoned")
@@ -325,12 +515,16 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
     /// Counts segments
     #[must_use]
     pub fn len(&self) -> usize {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         self.segments.read().expect("lock is poisoned").len()
     }
 
     /// Returns the amount of bytes on disk that are occupied by blobs.
     #[must_use]
     pub fn disk_space_used(&self) -> u64 {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         self.segments
             .read()
             .expect("lock is poisoned")
@@ -342,6 +536,8 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
     /// Returns the amount of stale bytes
     #[must_use]
     pub fn total_bytes(&self) -> u64 {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         self.segments
             .read()
             .expect("lock is poisoned")
@@ -353,6 +549,8 @@ impl<C: Compressor + Clone> SegmentManifest<C> {
     /// Returns the amount of stale bytes
     #[must_use]
     pub fn stale_bytes(&self) -> u64 {
+        // NOTE: Lock is only poisoned if a thread panicked while holding it
+        #[allow(clippy::expect_used)]
         self.segments
             .read()
             .expect("lock is poisoned")
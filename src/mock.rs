@@ -39,9 +39,21 @@ impl IndexReader for MockIndex {
             .map(|(vhandle, _)| vhandle)
             .cloned())
     }
+
+    fn get_many(&self, keys: &[&[u8]]) -> std::io::Result<Vec<Option<ValueHandle>>> {
+        let lock = self.read().expect("lock is poisoned");
+
+        Ok(keys
+            .iter()
+            .map(|key| lock.get(*key).map(|(vhandle, _)| vhandle).cloned())
+            .collect())
+    }
 }
 
 /// Used for tests only
+///
+/// Inserts are applied immediately, conditional inserts are compared
+/// and applied atomically under the index lock.
 #[allow(clippy::module_name_repetitions)]
 pub struct MockIndexWriter(pub MockIndex);
 
@@ -59,7 +71,140 @@ impl IndexWriter for MockIndexWriter {
         Ok(())
     }
 
+    #[allow(clippy::significant_drop_tightening)]
+    fn insert_indirect_if(
+        &mut self,
+        key: &[u8],
+        expected: &ValueHandle,
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> std::io::Result<()> {
+        let mut lock = self.0.write().expect("lock is poisoned");
+
+        if let Some(item) = lock.get_mut(key) {
+            if &item.0 == expected {
+                *item = (vhandle, size);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Used for tests only
+///
+/// All inserts are buffered, and only applied in [`IndexWriter::finish`].
+/// Conditional inserts are compared when they are applied.
+#[allow(clippy::module_name_repetitions)]
+pub struct MockBatchIndexWriter {
+    index: MockIndex,
+    inserts: Vec<(UserKey, Option<ValueHandle>, ValueHandle, u32)>,
+}
+
+impl MockBatchIndexWriter {
+    /// Creates a writer for the given index
+    #[must_use]
+    pub fn new(index: MockIndex) -> Self {
+        Self {
+            index,
+            inserts: vec![],
+        }
+    }
+}
+
+impl IndexWriter for MockBatchIndexWriter {
+    fn insert_indirect(
+        &mut self,
+        key: &[u8],
+        value: ValueHandle,
+        size: u32,
+    ) -> std::io::Result<()> {
+        self.inserts.push((key.into(), None, value, size));
+        Ok(())
+    }
+
+    fn insert_indirect_if(
+        &mut self,
+        key: &[u8],
+        expected: &ValueHandle,
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> std::io::Result<()> {
+        self.inserts
+            .push((key.into(), Some(expected.clone()), vhandle, size));
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
     fn finish(&mut self) -> std::io::Result<()> {
+        let mut lock = self.index.write().expect("lock is poisoned");
+
+        for (key, expected, vhandle, size) in self.inserts.drain(..) {
+            match expected {
+                Some(expected) => {
+                    if let Some(item) = lock.get_mut(&key) {
+                        if item.0 == expected {
+                            *item = (vhandle, size);
+                        }
+                    }
+                }
+                None => {
+                    lock.insert(key, (vhandle, size));
+                }
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(feature = "async")]
+impl crate::AsyncIndexReader for MockIndex {
+    fn get<'a>(
+        &'a self,
+        key: &'a [u8],
+    ) -> crate::BoxFuture<'a, std::io::Result<Option<ValueHandle>>> {
+        Box::pin(std::future::ready(IndexReader::get(self, key)))
+    }
+
+    fn get_many<'a>(
+        &'a self,
+        keys: &'a [&'a [u8]],
+    ) -> crate::BoxFuture<'a, std::io::Result<Vec<Option<ValueHandle>>>> {
+        Box::pin(std::future::ready(IndexReader::get_many(self, keys)))
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::AsyncIndexWriter for MockIndexWriter {
+    fn insert_indirect<'a>(
+        &'a mut self,
+        key: &'a [u8],
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> crate::BoxFuture<'a, std::io::Result<()>> {
+        Box::pin(std::future::ready(IndexWriter::insert_indirect(
+            self, key, vhandle, size,
+        )))
+    }
+
+    fn insert_indirect_if<'a>(
+        &'a mut self,
+        key: &'a [u8],
+        expected: &'a ValueHandle,
+        vhandle: ValueHandle,
+        size: u32,
+    ) -> crate::BoxFuture<'a, std::io::Result<()>> {
+        Box::pin(std::future::ready(IndexWriter::insert_indirect_if(
+            self, key, expected, vhandle, size,
+        )))
+    }
+
+    fn finish(&mut self) -> crate::BoxFuture<'_, std::io::Result<()>> {
+        Box::pin(std::future::ready(IndexWriter::finish(self)))
+    }
+}
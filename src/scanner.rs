@@ -3,7 +3,7 @@
 // (found in the LICENSE-* files in the repository)
 
 use crate::{id::SegmentId, ValueHandle};
-use std::{collections::BTreeMap, sync::MutexGuard};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Default)]
 pub struct SegmentCounter {
@@ -14,28 +14,23 @@ pub struct SegmentCounter {
 pub type SizeMap = BTreeMap<SegmentId, SegmentCounter>;
 
 /// Scans a value log, building a size map for the GC report
-pub struct Scanner<'a, I: Iterator<Item = std::io::Result<(ValueHandle, u32)>>> {
+///
+/// Only the given segments are counted, so segments that are
+/// created while the scan is running do not get partial results.
+pub struct Scanner<I: Iterator<Item = std::io::Result<(ValueHandle, u32)>>> {
     iter: I,
-
-    #[allow(unused)]
-    lock_guard: MutexGuard<'a, ()>,
-
     size_map: SizeMap,
 }
 
-impl<'a, I: Iterator<Item = std::io::Result<(ValueHandle, u32)>>> Scanner<'a, I> {
-    pub fn new(iter: I, lock_guard: MutexGuard<'a, ()>, ids: &[SegmentId]) -> Self {
+impl<I: Iterator<Item = std::io::Result<(ValueHandle, u32)>>> Scanner<I> {
+    pub fn new(iter: I, ids: &[SegmentId]) -> Self {
         let mut size_map = BTreeMap::default();
 
         for &id in ids {
             size_map.insert(id, SegmentCounter::default());
         }
 
-        Self {
-            iter,
-            lock_guard,
-            size_map,
-        }
+        Self { iter, size_map }
     }
 
     pub fn finish(self) -> SizeMap {
@@ -52,16 +47,11 @@ impl<'a, I: Iterator<Item = std::io::Result<(ValueHandle, u32)>>> Scanner<'a, I>
             })?;
             let size = u64::from(size);
 
-            self.size_map
-                .entry(vhandle.segment_id)
-                .and_modify(|x| {
-                    x.item_count += 1;
-                    x.size += size;
-                })
-                .or_insert_with(|| SegmentCounter {
-                    size,
-                    item_count: 1,
-                });
+            // NOTE: Segments that are not part of the snapshot are ignored
+            if let Some(counter) = self.size_map.get_mut(&vhandle.segment_id) {
+                counter.item_count += 1;
+                counter.size += size;
+            }
         }
 
         Ok(())
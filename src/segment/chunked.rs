@@ -0,0 +1,531 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    coding::{Decode, DecodeError, Encode, EncodeError},
+    encryption::{associated_data, EncryptorRef},
+    id::SegmentId,
+    Compressor,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Read, Write};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Amount of (uncompressed) bytes per chunk of a chunked blob
+pub const BLOB_CHUNK_SIZE: u32 = 64 * 1_024;
+
+/// Chunk flag that is set if the chunk is stored compressed
+const CHUNK_FLAG_COMPRESSED: u8 = 0b0000_0001;
+
+/// Size of a chunk entry (flags, stored length, checksum)
+const CHUNK_ENTRY_SIZE: usize =
+    std::mem::size_of::<u8>() + std::mem::size_of::<u32>() + std::mem::size_of::<u64>();
+
+/// Table of contents of a chunked blob
+///
+/// The value of a chunked blob is stored as the table, followed by the chunks.
+/// Every chunk is compressed and encrypted on its own, so parts of the value
+/// can be read without decoding the entire value.
+///
+/// The table is followed by its own checksum, so it can be verified
+/// without reading the chunks.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChunkTable {
+    /// Amount of (uncompressed) bytes per chunk, except for the last chunk
+    pub chunk_size: u32,
+
+    /// Uncompressed length of the value
+    pub value_len: u32,
+
+    /// Chunks, in order
+    pub chunks: Vec<ChunkEntry>,
+}
+
+/// Entry of a [`ChunkTable`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChunkEntry {
+    /// `true` if the chunk is stored compressed
+    pub is_compressed: bool,
+
+    /// Size of the chunk on disk
+    pub stored_len: u32,
+
+    /// Checksum of the chunk on disk
+    pub checksum: u64,
+}
+
+impl ChunkTable {
+    /// Returns the offsets of the chunks, relative to the end of the table.
+    pub fn chunk_offsets(&self) -> Vec<u64> {
+        self.chunks
+            .iter()
+            .scan(0, |offset, chunk| {
+                let current = *offset;
+                *offset += u64::from(chunk.stored_len);
+                Some(current)
+            })
+            .collect()
+    }
+
+    /// Returns the (uncompressed) length of a chunk.
+    ///
+    /// Every chunk is `chunk_size` long, except for the last chunk,
+    /// which holds the remainder of the value.
+    pub fn chunk_len(&self, idx: usize) -> Option<usize> {
+        let start = u64::from(self.chunk_size) * idx as u64;
+        let remaining = u64::from(self.value_len).checked_sub(start)?;
+        let len = remaining.min(self.chunk_size.into());
+
+        // NOTE: Chunks are tiny
+        #[allow(clippy::cast_possible_truncation)]
+        (len > 0).then_some(len as usize)
+    }
+
+    /// Checks that the table and its chunks span exactly `stored_len` bytes.
+    ///
+    /// The stored chunk lengths are read from disk, so they need to be checked
+    /// before buffers are allocated for the chunks.
+    pub fn check_stored_len(&self, stored_len: u64) -> Result<(), DecodeError> {
+        let chunks_len = self
+            .chunks
+            .iter()
+            .map(|x| u64::from(x.stored_len))
+            .sum::<u64>();
+
+        if table_len(self.chunks.len()) as u64 + chunks_len == stored_len {
+            Ok(())
+        } else {
+            Err(DecodeError::InvalidHeader("ChunkTable"))
+        }
+    }
+
+    /// Computes the checksum of the table.
+    pub fn checksum(&self) -> crate::Result<u64> {
+        let mut bytes = Vec::with_capacity(table_len(self.chunks.len()));
+        self.encode_entries_into(&mut bytes)?;
+        Ok(xxhash_rust::xxh3::xxh3_64(&bytes))
+    }
+
+    /// Reads a table, and the checksum that is stored after it.
+    ///
+    /// The checksum is not verified, see [`ChunkTable::checksum`].
+    pub fn decode_with_checksum<R: Read>(reader: &mut R) -> Result<(Self, u64), DecodeError> {
+        let chunk_size = reader.read_u32::<BigEndian>()?;
+        let value_len = reader.read_u32::<BigEndian>()?;
+        let chunk_count = reader.read_u32::<BigEndian>()?;
+
+        // IMPORTANT: The chunk count is read from disk,
+        // so it needs to be checked before allocating anything
+        if expected_chunk_count(chunk_size, value_len) != Some(chunk_count) {
+            return Err(DecodeError::InvalidHeader("ChunkTable"));
+        }
+
+        let mut chunks = Vec::with_capacity(chunk_count as usize);
+
+        for _ in 0..chunk_count {
+            let is_compressed = match reader.read_u8()? {
+                0 => false,
+                CHUNK_FLAG_COMPRESSED => true,
+                tag => return Err(DecodeError::InvalidTag(("ChunkFlags", tag))),
+            };
+
+            chunks.push(ChunkEntry {
+                is_compressed,
+                stored_len: reader.read_u32::<BigEndian>()?,
+                checksum: reader.read_u64::<BigEndian>()?,
+            });
+        }
+
+        let checksum = reader.read_u64::<BigEndian>()?;
+
+        Ok((
+            Self {
+                chunk_size,
+                value_len,
+                chunks,
+            },
+            checksum,
+        ))
+    }
+
+    fn encode_entries_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        writer.write_u32::<BigEndian>(self.chunk_size)?;
+        writer.write_u32::<BigEndian>(self.value_len)?;
+
+        // NOTE: Chunk count is limited by the value length (u32)
+        #[allow(clippy::cast_possible_truncation)]
+        writer.write_u32::<BigEndian>(self.chunks.len() as u32)?;
+
+        for chunk in &self.chunks {
+            writer.write_u8(if chunk.is_compressed {
+                CHUNK_FLAG_COMPRESSED
+            } else {
+                0
+            })?;
+            writer.write_u32::<BigEndian>(chunk.stored_len)?;
+            writer.write_u64::<BigEndian>(chunk.checksum)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Encode for ChunkTable {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        let mut bytes = Vec::with_capacity(table_len(self.chunks.len()));
+        self.encode_entries_into(&mut bytes)?;
+
+        writer.write_all(&bytes)?;
+        writer.write_u64::<BigEndian>(xxhash_rust::xxh3::xxh3_64(&bytes))?;
+
+        Ok(())
+    }
+}
+
+impl Decode for ChunkTable {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        Self::decode_with_checksum(reader).map(|(table, _)| table)
+    }
+}
+
+/// Returns the amount of chunks a value of the given length is split into.
+///
+/// Returns `None` if the chunk size is invalid.
+fn expected_chunk_count(chunk_size: u32, value_len: u32) -> Option<u32> {
+    (chunk_size > 0).then(|| value_len.div_ceil(chunk_size))
+}
+
+/// Returns the encoded size of a chunk table with the given amount of chunks.
+pub fn table_len(chunk_count: usize) -> usize {
+    3 * std::mem::size_of::<u32>() + chunk_count * CHUNK_ENTRY_SIZE + std::mem::size_of::<u64>()
+}
+
+/// Finishes the checksum of a chunked blob, given the hasher
+/// that has already hashed the blob header and the key.
+///
+/// Unlike other blobs, the chunks are hashed before the chunk table,
+/// so the checksum can be computed while the chunks are written,
+/// before the table is known.
+pub fn checksum(mut hasher: Xxh3, stored: &[u8]) -> u64 {
+    let read_u32 = |pos: usize| {
+        stored
+            .get(pos..pos + std::mem::size_of::<u32>())
+            .and_then(|x| x.try_into().ok())
+            .map(u32::from_be_bytes)
+    };
+
+    // NOTE: A corrupted chunk count is hashed as part of the table,
+    // which will then not match the stored checksum
+    let table_end = match (read_u32(0), read_u32(4), read_u32(8)) {
+        (Some(chunk_size), Some(value_len), Some(chunk_count))
+            if expected_chunk_count(chunk_size, value_len) == Some(chunk_count) =>
+        {
+            table_len(chunk_count as usize).min(stored.len())
+        }
+        _ => stored.len(),
+    };
+
+    let (table, data) = stored.split_at(table_end);
+
+    hasher.update(data);
+    hasher.update(table);
+    hasher.digest()
+}
+
+/// Compresses and encrypts a single chunk.
+///
+/// The chunk is encrypted with the blob key, the segment ID and the chunk index
+/// as associated data, so it can only be decrypted at its position.
+///
+/// `is_worth_compressing` decides if a compressed chunk is stored compressed,
+/// given the raw and compressed length.
+pub fn encode_chunk<C: Compressor>(
+    chunk: &[u8],
+    chunk_idx: u32,
+    key: &[u8],
+    segment_id: SegmentId,
+    compressor: Option<&C>,
+    encryption: Option<&(EncryptorRef, u32)>,
+    is_worth_compressing: impl Fn(usize, usize) -> bool,
+) -> crate::Result<(Vec<u8>, ChunkEntry)> {
+    let (mut stored, is_compressed) = match compressor {
+        Some(compressor) => {
+            let compressed = compressor.compress(chunk)?;
+
+            if is_worth_compressing(chunk.len(), compressed.len()) {
+                (compressed, true)
+            } else {
+                (chunk.to_vec(), false)
+            }
+        }
+        None => (chunk.to_vec(), false),
+    };
+
+    if let Some((encryptor, key_id)) = encryption {
+        let associated_data = associated_data(key, segment_id, Some(chunk_idx));
+        stored = encryptor.encrypt(*key_id, &stored, &associated_data)?;
+    }
+
+    let entry = ChunkEntry {
+        is_compressed,
+
+        // NOTE: Chunks are tiny
+        #[allow(clippy::cast_possible_truncation)]
+        stored_len: stored.len() as u32,
+
+        checksum: xxhash_rust::xxh3::xxh3_64(&stored),
+    };
+
+    Ok((stored, entry))
+}
+
+/// Encodes a value into chunks, compressing and encrypting every chunk on its own.
+///
+/// See [`encode_chunk`].
+pub fn encode_chunked<C: Compressor>(
+    value: &[u8],
+    key: &[u8],
+    segment_id: SegmentId,
+    compressor: Option<&C>,
+    encryption: Option<&(EncryptorRef, u32)>,
+    is_worth_compressing: impl Fn(usize, usize) -> bool,
+) -> crate::Result<Vec<u8>> {
+    let mut table = ChunkTable {
+        chunk_size: BLOB_CHUNK_SIZE,
+
+        // NOTE: Values are u32 max
+        #[allow(clippy::cast_possible_truncation)]
+        value_len: value.len() as u32,
+
+        chunks: Vec::with_capacity(value.len().div_ceil(BLOB_CHUNK_SIZE as usize)),
+    };
+
+    let mut data = Vec::with_capacity(value.len());
+
+    for (idx, chunk) in value.chunks(BLOB_CHUNK_SIZE as usize).enumerate() {
+        // NOTE: Chunk count is limited by the value length (u32)
+        #[allow(clippy::cast_possible_truncation)]
+        let (stored, entry) = encode_chunk(
+            chunk,
+            idx as u32,
+            key,
+            segment_id,
+            compressor,
+            encryption,
+            &is_worth_compressing,
+        )?;
+
+        table.chunks.push(entry);
+        data.extend_from_slice(&stored);
+    }
+
+    let mut bytes = Vec::with_capacity(table_len(table.chunks.len()) + data.len());
+    table.encode_into(&mut bytes)?;
+    bytes.extend_from_slice(&data);
+
+    Ok(bytes)
+}
+
+/// Decodes a single chunk, as stored on disk.
+///
+/// The decoded chunk is checked against the chunk length given by the table.
+///
+/// See [`encode_chunk`].
+pub fn decode_chunk<C: Compressor>(
+    table: &ChunkTable,
+    chunk_idx: u32,
+    mut stored: Vec<u8>,
+    key: &[u8],
+    segment_id: SegmentId,
+    compressor: Option<&C>,
+    encryption: Option<&(EncryptorRef, u32)>,
+) -> crate::Result<Vec<u8>> {
+    let idx = chunk_idx as usize;
+
+    let (Some(chunk), Some(expected_len)) = (table.chunks.get(idx), table.chunk_len(idx)) else {
+        return Err(crate::Error::Decode(DecodeError::InvalidHeader(
+            "ChunkTable",
+        )));
+    };
+
+    if let Some((encryptor, key_id)) = encryption {
+        let associated_data = associated_data(key, segment_id, Some(chunk_idx));
+        stored = encryptor.decrypt(*key_id, &stored, &associated_data)?;
+    }
+
+    let chunk_bytes = match compressor {
+        Some(compressor) if chunk.is_compressed => compressor.decompress(&stored)?,
+        _ => stored,
+    };
+
+    // IMPORTANT: Reads compute the position in the value from the chunk size,
+    // so a chunk of a different length would shift all following bytes
+    if chunk_bytes.len() != expected_len {
+        return Err(crate::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "chunk #{chunk_idx} has length {}, expected {expected_len}",
+                chunk_bytes.len(),
+            ),
+        )));
+    }
+
+    Ok(chunk_bytes)
+}
+
+/// Decodes an entire chunked value, as stored on disk.
+pub fn decode_chunked<C: Compressor>(
+    stored: &[u8],
+    key: &[u8],
+    segment_id: SegmentId,
+    compressor: Option<&C>,
+    encryption: Option<&(EncryptorRef, u32)>,
+) -> crate::Result<Vec<u8>> {
+    let mut reader = Cursor::new(stored);
+    let table = ChunkTable::decode_from(&mut reader)?;
+    table.check_stored_len(stored.len() as u64)?;
+
+    let mut value = Vec::with_capacity(table.value_len as usize);
+
+    for (idx, chunk) in table.chunks.iter().enumerate() {
+        let mut stored = vec![0; chunk.stored_len as usize];
+        reader.read_exact(&mut stored)?;
+
+        // NOTE: Chunk count is limited by the value length (u32)
+        #[allow(clippy::cast_possible_truncation)]
+        value.extend(decode_chunk(
+            &table, idx as u32, stored, key, segment_id, compressor, encryption,
+        )?);
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[derive(Clone)]
+    struct HalvingCompressor;
+
+    impl Compressor for HalvingCompressor {
+        fn compress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+            Ok(bytes.iter().step_by(2).copied().collect())
+        }
+
+        fn decompress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+            Ok(bytes.iter().flat_map(|&x| [x, x]).collect())
+        }
+    }
+
+    #[test]
+    fn chunked_roundtrip() -> crate::Result<()> {
+        let value = (0..200_000u32)
+            .flat_map(|x| [(x / 2) as u8, (x / 2) as u8])
+            .collect::<Vec<_>>();
+
+        let stored = encode_chunked(&value, b"a", 0, Some(&HalvingCompressor), None, |_, _| true)?;
+
+        let table = ChunkTable::decode_from(&mut Cursor::new(&stored))?;
+        assert_eq!(BLOB_CHUNK_SIZE, table.chunk_size);
+        assert_eq!(value.len() as u32, table.value_len);
+        assert_eq!(7, table.chunks.len());
+        assert!(table.chunks.iter().all(|x| x.is_compressed));
+        assert_eq!(
+            vec![0, 32_768, 65_536],
+            table
+                .chunk_offsets()
+                .into_iter()
+                .take(3)
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(
+            value,
+            decode_chunked(&stored, b"a", 0, Some(&HalvingCompressor), None)?,
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunked_decoded_len_mismatch() -> crate::Result<()> {
+        let value = vec![0; 200_000];
+        let stored = encode_chunked(&value, b"a", 0, Some(&HalvingCompressor), None, |_, _| true)?;
+
+        // NOTE: Every chunk is stored compressed, so skipping decompression
+        // returns chunks that are half as long as expected
+        let table = ChunkTable::decode_from(&mut Cursor::new(&stored))?;
+        let chunk = stored[table_len(table.chunks.len())..]
+            .get(..table.chunks[0].stored_len as usize)
+            .unwrap()
+            .to_vec();
+
+        assert!(matches!(
+            decode_chunk::<HalvingCompressor>(&table, 0, chunk, b"a", 0, None, None),
+            Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::InvalidData,
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunked_stored_len_out_of_bounds() -> crate::Result<()> {
+        let value = vec![0; 200_000];
+        let mut stored =
+            encode_chunked(&value, b"a", 0, Some(&HalvingCompressor), None, |_, _| true)?;
+
+        // NOTE: Corrupt the stored length of the first chunk
+        stored[13..17].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        assert!(matches!(
+            decode_chunked(&stored, b"a", 0, Some(&HalvingCompressor), None),
+            Err(crate::Error::Decode(DecodeError::InvalidHeader(
+                "ChunkTable"
+            ))),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunked_table_invalid_chunk_count() -> crate::Result<()> {
+        let value = vec![0; 200_000];
+        let mut stored =
+            encode_chunked(&value, b"a", 0, Some(&HalvingCompressor), None, |_, _| true)?;
+
+        // NOTE: Corrupt the chunk count
+        stored[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        assert!(matches!(
+            ChunkTable::decode_from(&mut Cursor::new(&stored)),
+            Err(DecodeError::InvalidHeader("ChunkTable")),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunked_table_checksum() -> crate::Result<()> {
+        let value = vec![0; 200_000];
+        let mut stored =
+            encode_chunked(&value, b"a", 0, Some(&HalvingCompressor), None, |_, _| true)?;
+
+        let (table, checksum) = ChunkTable::decode_with_checksum(&mut Cursor::new(&stored))?;
+        assert_eq!(
+            table_len(table.chunks.len()),
+            table.encode_into_vec()?.len()
+        );
+        assert_eq!(checksum, table.checksum()?);
+
+        // NOTE: Corrupt the stored length of the first chunk
+        stored[13] ^= 0xFF;
+
+        let (table, checksum) = ChunkTable::decode_with_checksum(&mut Cursor::new(&stored))?;
+        assert_ne!(checksum, table.checksum()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+
+/// Maximum amount of buckets in an expiry histogram
+pub const MAX_EXPIRY_BUCKETS: usize = 32;
+
+/// Expiring blobs of a segment, grouped by their expiry timestamp
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct ExpiryHistogram(Vec<ExpiryBucket>);
+
+/// Bucket of an [`ExpiryHistogram`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct ExpiryBucket {
+    /// Unix timestamp (in milliseconds) at which all blobs in the bucket have expired
+    pub expires_at: u64,
+
+    /// Amount of bytes (uncompressed) of the blobs in the bucket
+    pub bytes: u64,
+}
+
+impl ExpiryHistogram {
+    /// Adds a blob with the given expiry timestamp and size.
+    ///
+    /// If there are too many buckets, the two adjacent buckets with the
+    /// least bytes are merged, so the histogram never grows beyond
+    /// [`MAX_EXPIRY_BUCKETS`].
+    pub fn insert(&mut self, expires_at: u64, bytes: u64) {
+        match self.0.binary_search_by_key(&expires_at, |x| x.expires_at) {
+            Ok(idx) => {
+                if let Some(bucket) = self.0.get_mut(idx) {
+                    bucket.bytes += bytes;
+                }
+            }
+            Err(idx) => {
+                self.0.insert(idx, ExpiryBucket { expires_at, bytes });
+            }
+        }
+
+        if self.0.len() > MAX_EXPIRY_BUCKETS {
+            let Some(idx) = self
+                .0
+                .windows(2)
+                .enumerate()
+                .min_by_key(|(_, pair)| pair.iter().map(|x| x.bytes).sum::<u64>())
+                .map(|(idx, _)| idx)
+            else {
+                return;
+            };
+
+            // NOTE: The merged bucket has only expired when its later bucket has expired
+            let merged = self.0.remove(idx);
+
+            if let Some(bucket) = self.0.get_mut(idx) {
+                bucket.bytes += merged.bytes;
+            }
+        }
+    }
+
+    /// Returns the buckets, ordered by expiry timestamp.
+    #[must_use]
+    pub fn buckets(&self) -> &[ExpiryBucket] {
+        &self.0
+    }
+
+    /// Returns the amount of bytes that have definitely
+    /// expired at the given unix timestamp (in milliseconds).
+    #[must_use]
+    pub fn expired_bytes(&self, now: u64) -> u64 {
+        self.0
+            .iter()
+            .take_while(|x| x.expires_at <= now)
+            .map(|x| x.bytes)
+            .sum()
+    }
+}
+
+impl Encode for ExpiryHistogram {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        // NOTE: Bucket count is limited by MAX_EXPIRY_BUCKETS
+        #[allow(clippy::cast_possible_truncation)]
+        writer.write_u8(self.0.len() as u8)?;
+
+        for bucket in &self.0 {
+            writer.write_u64::<BigEndian>(bucket.expires_at)?;
+            writer.write_u64::<BigEndian>(bucket.bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for ExpiryHistogram {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        let len = reader.read_u8()?;
+
+        let mut buckets = Vec::with_capacity(len.into());
+
+        for _ in 0..len {
+            let expires_at = reader.read_u64::<BigEndian>()?;
+            let bytes = reader.read_u64::<BigEndian>()?;
+            buckets.push(ExpiryBucket { expires_at, bytes });
+        }
+
+        Ok(Self(buckets))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn expiry_histogram_buckets() {
+        let mut histogram = ExpiryHistogram::default();
+
+        for expires_at in (0..100).rev() {
+            histogram.insert(expires_at, 10);
+        }
+
+        assert!(histogram.buckets().len() <= MAX_EXPIRY_BUCKETS);
+        assert_eq!(
+            1_000,
+            histogram.buckets().iter().map(|x| x.bytes).sum::<u64>()
+        );
+
+        assert_eq!(0, histogram.expired_bytes(0));
+        assert_eq!(1_000, histogram.expired_bytes(99));
+
+        // NOTE: Only full buckets count as expired
+        let expired = histogram.expired_bytes(49);
+        assert!(expired > 400 && expired <= 500);
+    }
+
+    #[test]
+    fn expiry_histogram_merge_keeps_latest_expiry() {
+        let mut histogram = ExpiryHistogram::default();
+
+        for expires_at in 0..=MAX_EXPIRY_BUCKETS as u64 {
+            histogram.insert(expires_at, 10);
+        }
+        histogram.insert(5, 10);
+
+        assert_eq!(MAX_EXPIRY_BUCKETS, histogram.buckets().len());
+        assert!(histogram
+            .buckets()
+            .windows(2)
+            .all(|pair| pair[0].expires_at < pair[1].expires_at));
+
+        // NOTE: The merged bucket only expires with its latest blob
+        assert_eq!(0, histogram.expired_bytes(0));
+        assert_eq!(
+            (MAX_EXPIRY_BUCKETS as u64 + 2) * 10,
+            histogram.expired_bytes(MAX_EXPIRY_BUCKETS as u64),
+        );
+    }
+
+    #[test]
+    fn expiry_histogram_roundtrip() -> crate::Result<()> {
+        let mut histogram = ExpiryHistogram::default();
+        histogram.insert(5, 1);
+        histogram.insert(3, 2);
+        histogram.insert(7, 3);
+
+        let bytes = histogram.encode_into_vec()?;
+        let decoded = ExpiryHistogram::decode_from(&mut &bytes[..])?;
+        assert_eq!(histogram, decoded);
+
+        Ok(())
+    }
+}
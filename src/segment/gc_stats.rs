@@ -2,7 +2,7 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[derive(Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -22,6 +22,24 @@ impl GcStats {
             .store(x, std::sync::atomic::Ordering::Release);
     }
 
+    /// Adds reported stale items and bytes, clamping them to the segment's totals.
+    ///
+    /// Reports cannot be deduplicated, so the stale item count is kept below
+    /// the segment's item count. This way, reports alone never make a segment
+    /// fully stale (and thus droppable); that needs to be confirmed by a scan
+    /// or a rollover. Counts that are already higher are kept as they are.
+    pub(crate) fn add_stale(&self, items: u64, bytes: u64, max_items: u64, max_bytes: u64) {
+        let add = |counter: &AtomicU64, x: u64, max: u64| {
+            // NOTE: Closure always returns Some, so this cannot fail
+            let _ = counter.fetch_update(Ordering::AcqRel, Ordering::Acquire, |prev| {
+                Some(prev.max(prev.saturating_add(x).min(max)))
+            });
+        };
+
+        add(&self.stale_items, items, max_items.saturating_sub(1));
+        add(&self.stale_bytes, bytes, max_bytes);
+    }
+
     /// Returns the amount of dead items in the segment
     pub fn stale_items(&self) -> u64 {
         self.stale_items.load(std::sync::atomic::Ordering::Acquire)
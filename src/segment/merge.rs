@@ -2,7 +2,7 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{id::SegmentId, value::UserKey, Compressor, SegmentReader, UserValue};
+use crate::{id::SegmentId, value::UserKey, Compressor, SegmentReader, UserValue, ValueHandle};
 use interval_heap::IntervalHeap;
 use std::cmp::Reverse;
 
@@ -23,7 +23,9 @@ struct IteratorValue {
     key: UserKey,
     value: UserValue,
     segment_id: SegmentId,
+    offset: u64,
     checksum: u64,
+    expires_at: Option<u64>,
 }
 
 impl PartialEq for IteratorValue {
@@ -61,9 +63,10 @@ impl<C: Compressor + Clone> MergeReader<C> {
 
     fn advance_reader(&mut self, idx: usize) -> crate::Result<()> {
         let reader = self.readers.get_mut(idx).expect("iter should exist");
+        let offset = reader.get_offset()?;
 
         if let Some(value) = reader.next() {
-            let (k, v, checksum) = value?;
+            let (k, v, checksum, expires_at) = value?;
             let segment_id = reader.segment_id;
 
             self.heap.push(IteratorValue {
@@ -71,7 +74,9 @@ impl<C: Compressor + Clone> MergeReader<C> {
                 key: k,
                 value: v,
                 segment_id,
+                offset,
                 checksum,
+                expires_at,
             });
         }
 
@@ -88,20 +93,27 @@ impl<C: Compressor + Clone> MergeReader<C> {
 }
 
 impl<C: Compressor + Clone> Iterator for MergeReader<C> {
-    type Item = crate::Result<(UserKey, UserValue, SegmentId, u64)>;
+    /// Key, value, value handle, checksum and expiry timestamp of a blob
+    type Item = crate::Result<(UserKey, UserValue, ValueHandle, u64, Option<u64>)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.heap.is_empty() {
             fail_iter!(self.push_next());
         }
 
-        if let Some(head) = self.heap.pop_min() {
+        if let Some(mut head) = self.heap.pop_min() {
             fail_iter!(self.advance_reader(head.index));
 
             // Discard old items
             while let Some(next) = self.heap.pop_min() {
                 if next.key == head.key {
                     fail_iter!(self.advance_reader(next.index));
+
+                    // NOTE: A key may be written multiple times into the same segment,
+                    // in which case the later blob is the newer one
+                    if next.index == head.index {
+                        head = next;
+                    }
                 } else {
                     // Reached next user key now
                     // Push back non-conflicting item and exit
@@ -110,7 +122,16 @@ impl<C: Compressor + Clone> Iterator for MergeReader<C> {
                 }
             }
 
-            return Some(Ok((head.key, head.value, head.segment_id, head.checksum)));
+            return Some(Ok((
+                head.key,
+                head.value,
+                ValueHandle {
+                    segment_id: head.segment_id,
+                    offset: head.offset,
+                },
+                head.checksum,
+                head.expires_at,
+            )));
         }
 
         None
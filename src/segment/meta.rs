@@ -2,18 +2,47 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+use super::expiry::ExpiryHistogram;
 use crate::{
     coding::{Decode, DecodeError, Encode, EncodeError},
+    compression::CompressionType,
     key_range::KeyRange,
+    version::Version,
 };
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{Read, Write};
+use xxhash_rust::xxh3::Xxh3;
 
-pub const METADATA_HEADER_MAGIC: &[u8] = &[b'V', b'L', b'O', b'G', b'S', b'M', b'D', 1];
+/// Metadata header magic, followed by the format version
+pub const METADATA_HEADER_MAGIC: &[u8] = b"VLOGSMD";
 
+/// Reader that hashes all bytes that are read through it
+struct HashingReader<'a, R: Read> {
+    inner: &'a mut R,
+    hasher: Xxh3,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        #[allow(clippy::indexing_slicing)]
+        self.hasher.update(&buf[..n]);
+
+        Ok(n)
+    }
+}
+
+/// Segment metadata
+///
+/// V2 metadata is followed by its checksum, because GC trusts it
+/// to drop expired segments without reading their blobs.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct Metadata {
+    /// Segment format version
+    pub version: Version,
+
     /// Number of KV-pairs in the segment
     pub item_count: u64,
 
@@ -25,12 +54,41 @@ pub struct Metadata {
 
     /// Key range
     pub key_range: KeyRange,
+
+    /// Compression the blobs were written with
+    ///
+    /// `None` if the blobs are not compressed.
+    ///
+    /// V1 segments do not record their compression, so it is unknown.
+    pub compression: Option<CompressionType>,
+
+    /// ID of the key the blobs are encrypted with
+    ///
+    /// `None` if the blobs are not encrypted.
+    pub encryption_key_id: Option<u32>,
+
+    /// Unix timestamp (in milliseconds) of when the segment's data was written
+    ///
+    /// V1 segments do not record it, so the file's modification time is used.
+    pub created_at: u64,
+
+    /// Unix timestamp (in milliseconds) after which all blobs have expired
+    ///
+    /// `None` if any blob does not expire.
+    pub max_expiry: Option<u64>,
+
+    /// Expiry timestamps of the blobs that expire
+    pub expiry_histogram: ExpiryHistogram,
 }
 
 impl Encode for Metadata {
-    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+    fn encode_into<W: Write>(&self, out: &mut W) -> Result<(), EncodeError> {
+        let mut bytes = vec![];
+        let writer = &mut bytes;
+
         // Write header
         writer.write_all(METADATA_HEADER_MAGIC)?;
+        writer.write_u8(self.version.into())?;
 
         writer.write_u64::<BigEndian>(self.item_count)?;
         writer.write_u64::<BigEndian>(self.compressed_bytes)?;
@@ -38,12 +96,59 @@ impl Encode for Metadata {
 
         self.key_range.encode_into(writer)?;
 
+        if self.version == Version::V2 {
+            match &self.compression {
+                Some(compression) => {
+                    writer.write_u8(1)?;
+                    compression.encode_into(writer)?;
+                }
+                None => {
+                    writer.write_u8(0)?;
+                }
+            }
+
+            match self.encryption_key_id {
+                Some(key_id) => {
+                    writer.write_u8(1)?;
+                    writer.write_u32::<BigEndian>(key_id)?;
+                }
+                None => {
+                    writer.write_u8(0)?;
+                }
+            }
+
+            writer.write_u64::<BigEndian>(self.created_at)?;
+
+            match self.max_expiry {
+                Some(max_expiry) => {
+                    writer.write_u8(1)?;
+                    writer.write_u64::<BigEndian>(max_expiry)?;
+                }
+                None => {
+                    writer.write_u8(0)?;
+                }
+            }
+
+            self.expiry_histogram.encode_into(writer)?;
+        }
+
+        out.write_all(&bytes)?;
+
+        if self.version == Version::V2 {
+            out.write_u64::<BigEndian>(xxhash_rust::xxh3::xxh3_64(&bytes))?;
+        }
+
         Ok(())
     }
 }
 
 impl Decode for Metadata {
     fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        let reader = &mut HashingReader {
+            inner: reader,
+            hasher: Xxh3::new(),
+        };
+
         // Check header
         let mut magic = [0u8; METADATA_HEADER_MAGIC.len()];
         reader.read_exact(&mut magic)?;
@@ -52,17 +157,71 @@ impl Decode for Metadata {
             return Err(DecodeError::InvalidHeader("SegmentMetadata"));
         }
 
+        let version = reader.read_u8()?;
+        let version = Version::try_from(version)
+            .map_err(|()| DecodeError::InvalidTag(("SegmentVersion", version)))?;
+
         let item_count = reader.read_u64::<BigEndian>()?;
         let compressed_bytes = reader.read_u64::<BigEndian>()?;
         let total_uncompressed_bytes = reader.read_u64::<BigEndian>()?;
 
         let key_range = KeyRange::decode_from(reader)?;
 
+        let (compression, encryption_key_id, created_at, max_expiry, expiry_histogram) =
+            match version {
+                // NOTE: The creation time needs to be filled in from the segment file
+                Version::V1 => (None, None, 0, None, ExpiryHistogram::default()),
+                Version::V2 => {
+                    let compression = match reader.read_u8()? {
+                        0 => None,
+                        1 => Some(CompressionType::decode_from(reader)?),
+                        tag => return Err(DecodeError::InvalidTag(("SegmentCompression", tag))),
+                    };
+
+                    let encryption_key_id = match reader.read_u8()? {
+                        0 => None,
+                        1 => Some(reader.read_u32::<BigEndian>()?),
+                        tag => return Err(DecodeError::InvalidTag(("SegmentEncryption", tag))),
+                    };
+
+                    let created_at = reader.read_u64::<BigEndian>()?;
+
+                    let max_expiry = match reader.read_u8()? {
+                        0 => None,
+                        1 => Some(reader.read_u64::<BigEndian>()?),
+                        tag => return Err(DecodeError::InvalidTag(("SegmentExpiry", tag))),
+                    };
+
+                    let expiry_histogram = ExpiryHistogram::decode_from(reader)?;
+
+                    let checksum = reader.inner.read_u64::<BigEndian>()?;
+
+                    // NOTE: The offset is filled in by the segment trailer
+                    if reader.hasher.digest() != checksum {
+                        return Err(DecodeError::InvalidChecksum(("SegmentMetadata", 0)));
+                    }
+
+                    (
+                        compression,
+                        encryption_key_id,
+                        created_at,
+                        max_expiry,
+                        expiry_histogram,
+                    )
+                }
+            };
+
         Ok(Self {
+            version,
             item_count,
             compressed_bytes,
             total_uncompressed_bytes,
             key_range,
+            compression,
+            encryption_key_id,
+            created_at,
+            max_expiry,
+            expiry_histogram,
         })
     }
 }
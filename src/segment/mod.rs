@@ -2,6 +2,8 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+pub mod chunked;
+pub mod expiry;
 pub mod gc_stats;
 pub mod merge;
 pub mod meta;
@@ -10,10 +12,10 @@ pub mod reader;
 pub mod trailer;
 pub mod writer;
 
-use crate::{id::SegmentId, Compressor};
+use crate::{id::SegmentId, version::Version, Compressor};
 use gc_stats::GcStats;
 use meta::Metadata;
-use std::{marker::PhantomData, path::PathBuf};
+use std::path::PathBuf;
 
 /// A disk segment is an immutable, sorted, contiguous file
 /// that contains key-value pairs.
@@ -31,10 +33,33 @@ pub struct Segment<C: Compressor + Clone> {
     /// Runtime stats for garbage collection
     pub gc_stats: GcStats,
 
-    pub(crate) _phantom: PhantomData<C>,
+    /// Compressor to decompress the segment's blobs with
+    pub(crate) decompressor: Option<C>,
 }
 
 impl<C: Compressor + Clone> Segment<C> {
+    /// Picks the compressor to decompress a segment's blobs with,
+    /// based on the compression recorded in its metadata.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the compressor does not support the segment's compression type.
+    pub(crate) fn resolve_decompressor(
+        meta: &Metadata,
+        compressor: &C,
+    ) -> crate::Result<Option<C>> {
+        match (meta.version, &meta.compression) {
+            // NOTE: V1 segments do not know their compression, so
+            // we need to trust the configured compressor
+            (Version::V1, _) => Ok(Some(compressor.clone())),
+            (Version::V2, None) => Ok(None),
+            (Version::V2, Some(compression_type)) => compressor
+                .decompressor_for(compression_type)
+                .map(Some)
+                .ok_or_else(|| crate::Error::UnsupportedCompression(compression_type.clone())),
+        }
+    }
+
     /// Returns a scanner that can iterate through the segment.
     ///
     /// # Errors
@@ -49,6 +74,33 @@ impl<C: Compressor + Clone> Segment<C> {
         false
     }
 
+    /// Returns the unix timestamp (in milliseconds) of when the segment's data was written.
+    ///
+    /// Segments that are rewritten by garbage collection keep the
+    /// creation time of the youngest segment they were rewritten from.
+    pub fn created_at(&self) -> u64 {
+        self.meta.created_at
+    }
+
+    /// Returns `true` if all blobs in the segment have expired
+    /// at the given unix timestamp (in milliseconds).
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.meta.max_expiry.is_some_and(|x| x <= now)
+    }
+
+    /// Returns the ratio of bytes that have definitely expired
+    /// at the given unix timestamp (in milliseconds).
+    // NOTE: Precision is not important here
+    #[allow(clippy::cast_precision_loss)]
+    pub fn expired_ratio(&self, now: u64) -> f32 {
+        let expired = self.meta.expiry_histogram.expired_bytes(now) as f32;
+        if expired == 0.0 {
+            return 0.0;
+        }
+
+        (expired / self.meta.total_uncompressed_bytes.max(1) as f32).min(1.0)
+    }
+
     /// Returns the amount of items in the segment.
     pub fn len(&self) -> u64 {
         self.meta.item_count
@@ -5,10 +5,17 @@
 use super::writer::Writer;
 use crate::{
     compression::Compressor,
+    encryption::EncryptorRef,
     id::{IdGenerator, SegmentId},
+    time::unix_timestamp_millis,
+    value::UserKey,
     ValueHandle,
 };
-use std::path::{Path, PathBuf};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 /// Segment writer, may write multiple segments
 pub struct MultiWriter<C: Compressor + Clone> {
@@ -20,6 +27,16 @@ pub struct MultiWriter<C: Compressor + Clone> {
     id_generator: IdGenerator,
 
     compression: Option<C>,
+    min_compression_savings: f32,
+    encryption: Option<EncryptorRef>,
+    large_blob_threshold: u64,
+    created_at: Option<u64>,
+
+    /// Keys at which a new segment is started (sorted)
+    partition_boundaries: Vec<UserKey>,
+
+    /// Index of the next partition boundary that has not been crossed yet
+    next_boundary: usize,
 }
 
 impl<C: Compressor + Clone> MultiWriter<C> {
@@ -47,6 +64,13 @@ impl<C: Compressor + Clone> MultiWriter<C> {
             writers: vec![Writer::new(segment_path, segment_id)?],
 
             compression: None,
+            min_compression_savings: 0.0,
+            encryption: None,
+            large_blob_threshold: u64::MAX,
+            created_at: None,
+
+            partition_boundaries: Vec::new(),
+            next_boundary: 0,
         })
     }
 
@@ -59,6 +83,56 @@ impl<C: Compressor + Clone> MultiWriter<C> {
         self
     }
 
+    /// Sets the minimum ratio compression needs to save
+    /// to store a blob compressed
+    #[must_use]
+    pub(crate) fn use_min_compression_savings(mut self, ratio: f32) -> Self {
+        self.min_compression_savings = ratio;
+        self.get_active_writer_mut().min_compression_savings = ratio;
+        self
+    }
+
+    /// Sets the encryption method
+    #[must_use]
+    pub(crate) fn use_encryption(mut self, encryptor: Option<EncryptorRef>) -> Self {
+        self.get_active_writer_mut().encryption = encryptor.clone().map(|encryptor| {
+            let key_id = encryptor.key_id();
+            (encryptor, key_id)
+        });
+        self.encryption = encryptor;
+        self
+    }
+
+    /// Sets the value size above which compressed or encrypted values are stored in chunks
+    #[must_use]
+    pub(crate) fn use_large_blob_threshold(mut self, bytes: u64) -> Self {
+        self.get_active_writer_mut().large_blob_threshold = bytes;
+        self.large_blob_threshold = bytes;
+        self
+    }
+
+    /// Overrides the creation time of the written segments
+    #[must_use]
+    pub(crate) fn use_created_at(mut self, created_at: u64) -> Self {
+        self.get_active_writer_mut().created_at = created_at;
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Sets keys at which a new segment is started, regardless of the segment size
+    ///
+    /// Keys need to be written in ascending order for the segments
+    /// to have non-overlapping key ranges, see [`MultiWriter::enter_partition`].
+    #[must_use]
+    pub(crate) fn use_partition_boundaries(mut self, mut boundaries: Vec<UserKey>) -> Self {
+        boundaries.sort();
+        boundaries.dedup();
+
+        self.partition_boundaries = boundaries;
+        self.next_boundary = 0;
+        self
+    }
+
     #[doc(hidden)]
     #[must_use]
     pub fn get_active_writer(&self) -> &Writer<C> {
@@ -91,6 +165,11 @@ impl<C: Compressor + Clone> MultiWriter<C> {
     }
 
     #[must_use]
+    /// Returns the amount of bytes (compressed data) written into all segments.
+    pub(crate) fn written_bytes(&self) -> u64 {
+        self.writers.iter().map(|x| x.written_blob_bytes).sum()
+    }
+
     fn segment_id(&self) -> SegmentId {
         self.get_active_writer().segment_id()
     }
@@ -102,8 +181,15 @@ impl<C: Compressor + Clone> MultiWriter<C> {
         let new_segment_id = self.id_generator.next();
         let segment_path = self.folder.join(new_segment_id.to_string());
 
-        let new_writer =
-            Writer::new(segment_path, new_segment_id)?.use_compression(self.compression.clone());
+        let mut new_writer = Writer::new(segment_path, new_segment_id)?
+            .use_compression(self.compression.clone())
+            .use_min_compression_savings(self.min_compression_savings)
+            .use_encryption(self.encryption.clone())
+            .use_large_blob_threshold(self.large_blob_threshold);
+
+        if let Some(created_at) = self.created_at {
+            new_writer = new_writer.use_created_at(created_at);
+        }
 
         self.writers.push(new_writer);
 
@@ -120,14 +206,70 @@ impl<C: Compressor + Clone> MultiWriter<C> {
         key: K,
         value: V,
     ) -> crate::Result<u32> {
-        let key = key.as_ref();
-        let value = value.as_ref();
+        self.write_inner(key.as_ref(), value.as_ref(), None)
+    }
+
+    /// Writes an item that expires after the given time-to-live.
+    ///
+    /// Expired blobs are not returned by [`ValueLog::get`](crate::ValueLog::get), and are
+    /// discarded by garbage collection. Segments in which all blobs have expired are dropped
+    /// by [`ValueLog::drop_stale_segments`](crate::ValueLog::drop_stale_segments),
+    /// without consulting the index.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn write_with_ttl<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: V,
+        ttl: Duration,
+    ) -> crate::Result<u32> {
+        let ttl = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+        let expires_at = unix_timestamp_millis().saturating_add(ttl);
+        self.write_with_expiry(key, value, expires_at)
+    }
 
+    /// Writes an item that expires at the given unix timestamp (in milliseconds).
+    ///
+    /// See [`MultiWriter::write_with_ttl`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn write_with_expiry<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &mut self,
+        key: K,
+        value: V,
+        expires_at: u64,
+    ) -> crate::Result<u32> {
+        self.write_inner(key.as_ref(), value.as_ref(), Some(expires_at))
+    }
+
+    /// Writes an item, copying the value of the given length from a reader.
+    ///
+    /// Unlike [`MultiWriter::write`], large values (see
+    /// [`Config::large_blob_threshold`](crate::Config::large_blob_threshold))
+    /// are never held in memory as a whole, so arbitrarily large values
+    /// can be written with bounded memory usage.
+    ///
+    /// If the reader fails, or ends before `len` bytes are read, the partially
+    /// written blob is discarded, and the writer can still be used.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn write_stream<K: AsRef<[u8]>, R: Read>(
+        &mut self,
+        key: K,
+        mut reader: R,
+        len: u32,
+    ) -> crate::Result<u32> {
         let target_size = self.target_size;
 
         // Write actual value into segment
         let writer = self.get_active_writer_mut();
-        let bytes_written = writer.write(key, value)?;
+        let bytes_written = writer.write_stream(key.as_ref(), &mut reader, len, None)?;
 
         // Check for segment size target, maybe rotate to next writer
         if writer.offset() >= target_size {
@@ -138,6 +280,48 @@ impl<C: Compressor + Clone> MultiWriter<C> {
         Ok(bytes_written)
     }
 
+    fn write_inner(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        expires_at: Option<u64>,
+    ) -> crate::Result<u32> {
+        let target_size = self.target_size;
+
+        // Write actual value into segment
+        let writer = self.get_active_writer_mut();
+        let bytes_written = writer.write_with_expiry(key, value, expires_at)?;
+
+        // Check for segment size target, maybe rotate to next writer
+        if writer.offset() >= target_size {
+            writer.flush()?;
+            self.rotate()?;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Rotates to the next segment if the key crosses a partition boundary.
+    ///
+    /// Needs to be called before getting the [`ValueHandle`] of the key.
+    pub(crate) fn enter_partition(&mut self, key: &[u8]) -> crate::Result<()> {
+        let crossed = self
+            .partition_boundaries
+            .iter()
+            .skip(self.next_boundary)
+            .take_while(|boundary| &***boundary <= key)
+            .count();
+
+        self.next_boundary += crossed;
+
+        if crossed > 0 && self.get_active_writer().item_count > 0 {
+            self.get_active_writer_mut().flush()?;
+            self.rotate()?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn finish(mut self) -> crate::Result<Vec<Writer<C>>> {
         let writer = self.get_active_writer_mut();
 
@@ -2,14 +2,28 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use super::{meta::METADATA_HEADER_MAGIC, writer::BLOB_HEADER_MAGIC};
-use crate::{coding::DecodeError, id::SegmentId, value::UserKey, Compressor, Slice, UserValue};
+use super::{
+    chunked::{self, decode_chunked},
+    meta::METADATA_HEADER_MAGIC,
+    writer::{
+        blob_hasher, BLOB_FLAG_CHUNKED, BLOB_FLAG_COMPRESSED, BLOB_FLAG_EXPIRES,
+        BLOB_HEADER_MAGIC_V1, BLOB_HEADER_MAGIC_V2,
+    },
+};
+use crate::{
+    coding::DecodeError,
+    encryption::{associated_data, EncryptorRef},
+    id::SegmentId,
+    value::UserKey,
+    Compressor, Slice, UserValue,
+};
 use byteorder::{BigEndian, ReadBytesExt};
 use std::{
     fs::File,
     io::{BufReader, Read, Seek},
     path::Path,
 };
+use xxhash_rust::xxh3::Xxh3;
 
 macro_rules! fail_iter {
     ($e:expr) => {
@@ -20,12 +34,154 @@ macro_rules! fail_iter {
     };
 }
 
+/// Checks a blob against its stored checksum.
+///
+/// The reader is expected to be positioned right after the blob.
+fn verify_checksum<R: Seek>(
+    reader: &mut R,
+    segment_id: SegmentId,
+    header: &BlobHeader,
+    value: &[u8],
+) -> crate::Result<()> {
+    let got = if header.is_chunked {
+        chunked::checksum(header.hasher(), value)
+    } else {
+        let mut hasher = header.hasher();
+        hasher.update(value);
+        hasher.digest()
+    };
+
+    if got == header.checksum {
+        return Ok(());
+    }
+
+    let blob_size = header.header_len
+        + std::mem::size_of::<u64>()
+        + std::mem::size_of::<u16>()
+        + header.key.len()
+        + std::mem::size_of::<u32>()
+        + value.len();
+    let offset = reader.stream_position()? - blob_size as u64;
+
+    Err(crate::Error::ChecksumMismatch {
+        segment_id,
+        offset,
+        expected: header.checksum,
+        got,
+    })
+}
+
+/// Header of a blob, up to the value
+pub struct BlobHeader {
+    /// Length of the header magic, flags and expiry timestamp
+    pub header_len: usize,
+
+    /// Blob flags, `None` for V1 blobs
+    pub flags: Option<u8>,
+
+    /// `true` if the value is stored compressed
+    ///
+    /// V1 blobs do not know if they are compressed, so they are
+    /// decompressed if the segment uses compression.
+    pub is_compressed: bool,
+
+    /// `true` if the value is stored in chunks, see [`ChunkTable`](super::chunked::ChunkTable)
+    pub is_chunked: bool,
+
+    /// Unix timestamp (in milliseconds) at which the blob expires
+    pub expires_at: Option<u64>,
+
+    /// Checksum of the key and the value (as stored)
+    ///
+    /// For V2 blobs, the checksum also covers the header magic, flags and expiry timestamp.
+    pub checksum: u64,
+
+    /// Key of the blob
+    pub key: UserKey,
+
+    /// Length of the value (as stored)
+    pub value_len: u32,
+}
+
+impl BlobHeader {
+    /// Reads a blob header.
+    ///
+    /// Returns `None` if the end of the blobs (the segment metadata) is reached.
+    pub fn read_from<R: Read>(reader: &mut R) -> crate::Result<Option<Self>> {
+        let (header_len, flags, expires_at) = {
+            let mut buf = [0; BLOB_HEADER_MAGIC_V2.len()];
+            reader.read_exact(&mut buf)?;
+
+            if buf.starts_with(METADATA_HEADER_MAGIC) {
+                return Ok(None);
+            }
+
+            if buf == BLOB_HEADER_MAGIC_V2 {
+                let flags = reader.read_u8()?;
+
+                if flags & !(BLOB_FLAG_COMPRESSED | BLOB_FLAG_EXPIRES | BLOB_FLAG_CHUNKED) != 0 {
+                    return Err(crate::Error::Decode(DecodeError::InvalidTag((
+                        "BlobFlags",
+                        flags,
+                    ))));
+                }
+
+                let mut header_len = buf.len() + std::mem::size_of::<u8>();
+
+                let expires_at = if flags & BLOB_FLAG_EXPIRES == 0 {
+                    None
+                } else {
+                    header_len += std::mem::size_of::<u64>();
+                    Some(reader.read_u64::<BigEndian>()?)
+                };
+
+                (header_len, Some(flags), expires_at)
+            } else if buf == BLOB_HEADER_MAGIC_V1 {
+                (buf.len(), None, None)
+            } else {
+                return Err(crate::Error::Decode(DecodeError::InvalidHeader("Blob")));
+            }
+        };
+
+        let checksum = reader.read_u64::<BigEndian>()?;
+
+        let key_len = reader.read_u16::<BigEndian>()?;
+        let key = Slice::from_reader(reader, key_len as usize)?;
+
+        let value_len = reader.read_u32::<BigEndian>()?;
+
+        Ok(Some(Self {
+            header_len,
+            flags,
+            is_compressed: flags.map_or(true, |flags| flags & BLOB_FLAG_COMPRESSED != 0),
+            is_chunked: flags.is_some_and(|flags| flags & BLOB_FLAG_CHUNKED != 0),
+            expires_at,
+            checksum,
+            key,
+            value_len,
+        }))
+    }
+
+    /// Returns a hasher that has hashed the header and the key,
+    /// so the stored value can be checked against the checksum.
+    pub fn hasher(&self) -> Xxh3 {
+        let mut hasher = match self.flags {
+            Some(flags) => blob_hasher(flags, self.expires_at),
+            None => Xxh3::new(),
+        };
+        hasher.update(&self.key);
+        hasher
+    }
+}
+
 /// Reads through a segment in order.
-pub struct Reader<C: Compressor + Clone> {
+pub struct Reader<C: Compressor + Clone, R: Read + Seek = File> {
     pub(crate) segment_id: SegmentId,
-    inner: BufReader<File>,
+    inner: BufReader<R>,
     is_terminated: bool,
     compression: Option<C>,
+    encryption: Option<(EncryptorRef, u32)>,
+    verify_checksums: bool,
 }
 
 impl<C: Compressor + Clone> Reader<C> {
@@ -39,72 +195,151 @@ impl<C: Compressor + Clone> Reader<C> {
 
         Ok(Self::with_reader(segment_id, file_reader))
     }
+}
 
+impl<C: Compressor + Clone, R: Read + Seek> Reader<C, R> {
     pub(crate) fn get_offset(&mut self) -> std::io::Result<u64> {
         self.inner.stream_position()
     }
 
+    /// Moves the reader to the given offset.
+    ///
+    /// Forward seeks are relative, so already buffered data is not discarded.
+    pub(crate) fn seek_to(&mut self, offset: u64) -> std::io::Result<()> {
+        self.is_terminated = false;
+
+        let current = self.get_offset()?;
+
+        if let Some(delta) = offset
+            .checked_sub(current)
+            .and_then(|delta| i64::try_from(delta).ok())
+        {
+            self.inner.seek_relative(delta)
+        } else {
+            self.inner
+                .seek(std::io::SeekFrom::Start(offset))
+                .map(|_| ())
+        }
+    }
+
     /// Initializes a new segment reader.
     #[must_use]
-    pub fn with_reader(segment_id: SegmentId, file_reader: BufReader<File>) -> Self {
+    pub fn with_reader(segment_id: SegmentId, file_reader: BufReader<R>) -> Self {
         Self {
             segment_id,
             inner: file_reader,
             is_terminated: false,
             compression: None,
+            encryption: None,
+            verify_checksums: false,
         }
     }
 
-    pub(crate) fn use_compression(mut self, compressor: C) -> Self {
-        self.compression = Some(compressor);
+    pub(crate) fn use_compression(mut self, compressor: Option<C>) -> Self {
+        self.compression = compressor;
+        self
+    }
+
+    /// Sets the encryptor and key ID to decrypt blobs with
+    pub(crate) fn use_encryption(mut self, encryption: Option<(EncryptorRef, u32)>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Makes the reader check each blob against its stored checksum,
+    /// returning [`crate::Error::ChecksumMismatch`] on failure.
+    pub(crate) fn verify_checksums(mut self, verify: bool) -> Self {
+        self.verify_checksums = verify;
         self
     }
 }
 
-impl<C: Compressor + Clone> Iterator for Reader<C> {
-    type Item = crate::Result<(UserKey, UserValue, u64)>;
+/// Key, value, checksum and expiry timestamp of a blob
+pub type BlobItem = (UserKey, UserValue, u64, Option<u64>);
+
+impl<C: Compressor + Clone, R: Read + Seek> Iterator for Reader<C, R> {
+    type Item = crate::Result<BlobItem>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.is_terminated {
             return None;
         }
 
-        {
-            let mut buf = [0; BLOB_HEADER_MAGIC.len()];
-            fail_iter!(self.inner.read_exact(&mut buf));
+        let Some(header) = fail_iter!(BlobHeader::read_from(&mut self.inner)) else {
+            self.is_terminated = true;
+            return None;
+        };
 
-            if buf == METADATA_HEADER_MAGIC {
-                self.is_terminated = true;
-                return None;
-            }
+        let val_len = header.value_len;
+
+        let compressor = self.compression.as_ref().filter(|_| header.is_compressed);
+
+        // NOTE: Without a compressor or decryptor (e.g. when verifying),
+        // chunked values are returned as stored, like other values
+        let decode_chunks =
+            header.is_chunked && (self.compression.is_some() || self.encryption.is_some());
 
-            if buf != BLOB_HEADER_MAGIC {
-                return Some(Err(crate::Error::Decode(DecodeError::InvalidHeader(
-                    "Blob",
-                ))));
+        let val = if decode_chunks {
+            let mut val = vec![0; val_len as usize];
+            fail_iter!(self.inner.read_exact(&mut val));
+
+            if self.verify_checksums {
+                fail_iter!(verify_checksum(
+                    &mut self.inner,
+                    self.segment_id,
+                    &header,
+                    &val
+                ));
             }
-        }
 
-        let checksum = fail_iter!(self.inner.read_u64::<BigEndian>());
+            Slice::from(fail_iter!(decode_chunked(
+                &val,
+                &header.key,
+                self.segment_id,
+                self.compression.as_ref(),
+                self.encryption.as_ref()
+            )))
+        } else if compressor.is_some() || self.encryption.is_some() {
+            // TODO: https://github.com/PSeitz/lz4_flex/issues/166
+            let mut val = vec![0; val_len as usize];
+            fail_iter!(self.inner.read_exact(&mut val));
+
+            if self.verify_checksums {
+                fail_iter!(verify_checksum(
+                    &mut self.inner,
+                    self.segment_id,
+                    &header,
+                    &val
+                ));
+            }
 
-        let key_len = fail_iter!(self.inner.read_u16::<BigEndian>());
-        let key = fail_iter!(Slice::from_reader(&mut self.inner, key_len as usize));
+            if let Some((encryptor, key_id)) = &self.encryption {
+                let associated_data = associated_data(&header.key, self.segment_id, None);
+                val = fail_iter!(encryptor.decrypt(*key_id, &val, &associated_data));
+            }
 
-        let val_len = fail_iter!(self.inner.read_u32::<BigEndian>());
-        let val = match &self.compression {
-            Some(compressor) => {
-                // TODO: https://github.com/PSeitz/lz4_flex/issues/166
-                let mut val = vec![0; val_len as usize];
-                fail_iter!(self.inner.read_exact(&mut val));
-                Slice::from(fail_iter!(compressor.decompress(&val)))
+            if let Some(compressor) = compressor {
+                val = fail_iter!(compressor.decompress(&val));
             }
-            None => {
-                // NOTE: When not using compression, we can skip
-                // the intermediary heap allocation and read directly into a Slice
-                fail_iter!(Slice::from_reader(&mut self.inner, val_len as usize))
+
+            Slice::from(val)
+        } else {
+            // NOTE: When not using compression or encryption, we can skip
+            // the intermediary heap allocation and read directly into a Slice
+            let val = fail_iter!(Slice::from_reader(&mut self.inner, val_len as usize));
+
+            if self.verify_checksums {
+                fail_iter!(verify_checksum(
+                    &mut self.inner,
+                    self.segment_id,
+                    &header,
+                    &val
+                ));
             }
+
+            val
         };
 
-        Some(Ok((key, val, checksum)))
+        Some(Ok((header.key, val, header.checksum, header.expires_at)))
     }
 }
@@ -46,7 +46,12 @@ impl SegmentFileTrailer {
 
         // Jump to metadata and parse
         reader.seek(std::io::SeekFrom::Start(metadata_ptr))?;
-        let metadata = Metadata::decode_from(&mut reader)?;
+        let metadata = Metadata::decode_from(&mut reader).map_err(|e| match e {
+            DecodeError::InvalidChecksum((name, _)) => {
+                DecodeError::InvalidChecksum((name, metadata_ptr))
+            }
+            e => e,
+        })?;
 
         Ok(Self {
             metadata,
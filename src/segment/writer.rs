@@ -2,18 +2,63 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use super::{meta::Metadata, trailer::SegmentFileTrailer};
+use super::{
+    chunked::{self, encode_chunk, encode_chunked, ChunkTable, BLOB_CHUNK_SIZE},
+    expiry::ExpiryHistogram,
+    meta::Metadata,
+    trailer::SegmentFileTrailer,
+};
 use crate::{
-    coding::Encode, compression::Compressor, id::SegmentId, key_range::KeyRange, value::UserKey,
+    coding::Encode,
+    compression::Compressor,
+    encryption::{associated_data, EncryptorRef},
+    id::SegmentId,
+    key_range::KeyRange,
+    time::unix_timestamp_millis,
+    value::UserKey,
+    version::Version,
 };
 use byteorder::{BigEndian, WriteBytesExt};
 use std::{
+    borrow::Cow,
     fs::File,
-    io::{BufWriter, Seek, Write},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
 };
+use xxhash_rust::xxh3::Xxh3;
+
+pub const BLOB_HEADER_MAGIC_V1: &[u8] = &[b'V', b'L', b'G', b'B', b'L', b'O', b'B', 1];
+
+/// Blob header magic of V2 blobs, followed by the blob flags
+pub const BLOB_HEADER_MAGIC_V2: &[u8] = &[b'V', b'L', b'G', b'B', b'L', b'O', b'B', 2];
+
+/// Blob flag that is set if the value is stored compressed
+pub const BLOB_FLAG_COMPRESSED: u8 = 0b0000_0001;
+
+/// Blob flag that is set if the blob expires, followed by the expiry timestamp
+pub const BLOB_FLAG_EXPIRES: u8 = 0b0000_0010;
+
+/// Blob flag that is set if the value is stored in chunks
+///
+/// See [`ChunkTable`](super::chunked::ChunkTable).
+pub const BLOB_FLAG_CHUNKED: u8 = 0b0000_0100;
 
-pub const BLOB_HEADER_MAGIC: &[u8] = &[b'V', b'L', b'G', b'B', b'L', b'O', b'B', 1];
+/// Starts the checksum of a V2 blob.
+///
+/// The checksum covers the blob header (magic, flags and expiry timestamp),
+/// so a corrupted flag or expiry timestamp is detected like a corrupted value.
+/// The key and the value are hashed afterwards.
+pub fn blob_hasher(flags: u8, expires_at: Option<u64>) -> Xxh3 {
+    let mut hasher = Xxh3::new();
+    hasher.update(BLOB_HEADER_MAGIC_V2);
+    hasher.update(&[flags]);
+
+    if let Some(expires_at) = expires_at {
+        hasher.update(&expires_at.to_be_bytes());
+    }
+
+    hasher
+}
 
 /// Segment writer
 pub struct Writer<C: Compressor + Clone> {
@@ -33,6 +78,22 @@ pub struct Writer<C: Compressor + Clone> {
     pub(crate) last_key: Option<UserKey>,
 
     pub(crate) compression: Option<C>,
+    pub(crate) min_compression_savings: f32,
+
+    /// Encryptor and the key ID the segment is encrypted with
+    pub(crate) encryption: Option<(EncryptorRef, u32)>,
+
+    /// Values larger than this are stored in chunks, if they are compressed or encrypted
+    pub(crate) large_blob_threshold: u64,
+
+    /// Unix timestamp (in milliseconds) of when the segment's data was written
+    pub(crate) created_at: u64,
+
+    /// Latest expiry timestamp of any blob, `None` if a blob does not expire
+    pub(crate) max_expiry: Option<u64>,
+
+    /// Expiry timestamps and (uncompressed) sizes of the expiring blobs
+    expiry_histogram: ExpiryHistogram,
 }
 
 impl<C: Compressor + Clone> Writer<C> {
@@ -60,6 +121,12 @@ impl<C: Compressor + Clone> Writer<C> {
             last_key: None,
 
             compression: None,
+            min_compression_savings: 0.0,
+            encryption: None,
+            large_blob_threshold: u64::MAX,
+            created_at: unix_timestamp_millis(),
+            max_expiry: Some(0),
+            expiry_histogram: ExpiryHistogram::default(),
         })
     }
 
@@ -68,6 +135,41 @@ impl<C: Compressor + Clone> Writer<C> {
         self
     }
 
+    pub(crate) fn use_min_compression_savings(mut self, ratio: f32) -> Self {
+        self.min_compression_savings = ratio;
+        self
+    }
+
+    /// Sets the encryption method
+    ///
+    /// The whole segment is encrypted with the encryptor's current key.
+    pub(crate) fn use_encryption(mut self, encryptor: Option<EncryptorRef>) -> Self {
+        self.encryption = encryptor.map(|encryptor| {
+            let key_id = encryptor.key_id();
+            (encryptor, key_id)
+        });
+        self
+    }
+
+    /// Sets the value size above which compressed or encrypted values are stored in chunks
+    pub(crate) fn use_large_blob_threshold(mut self, bytes: u64) -> Self {
+        self.large_blob_threshold = bytes;
+        self
+    }
+
+    /// Overrides the creation time, e.g. to retain the age of rewritten data
+    pub(crate) fn use_created_at(mut self, created_at: u64) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
+    /// Returns `true` if compression saved enough space
+    /// to store the compressed value.
+    #[allow(clippy::cast_precision_loss)]
+    fn is_worth_compressing(&self, raw_len: usize, compressed_len: usize) -> bool {
+        compressed_len as f32 <= raw_len as f32 * (1.0 - self.min_compression_savings)
+    }
+
     /// Returns the current offset in the file.
     ///
     /// This can be used to index an item into an external `Index`.
@@ -92,6 +194,25 @@ impl<C: Compressor + Clone> Writer<C> {
     ///
     /// Panics if the key length is empty or greater than 2^16, or the value length is greater than 2^32.
     pub fn write(&mut self, key: &[u8], value: &[u8]) -> crate::Result<u32> {
+        self.write_with_expiry(key, value, None)
+    }
+
+    /// Writes an item into the file, which expires at the given
+    /// unix timestamp (in milliseconds)
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key length is empty or greater than 2^16, or the value length is greater than 2^32.
+    pub(crate) fn write_with_expiry(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        expires_at: Option<u64>,
+    ) -> crate::Result<u32> {
         assert!(!key.is_empty());
         assert!(key.len() <= u16::MAX.into());
         assert!(u32::try_from(value.len()).is_ok());
@@ -101,17 +222,80 @@ impl<C: Compressor + Clone> Writer<C> {
         }
         self.last_key = Some(key.into());
 
-        self.uncompressed_bytes += value.len() as u64;
+        let raw_len = value.len() as u64;
+        self.uncompressed_bytes += raw_len;
+
+        // NOTE: Large values are compressed and encrypted in chunks,
+        // so they can be streamed without decoding the entire value
+        let is_chunked = raw_len > self.large_blob_threshold
+            && (self.compression.is_some() || self.encryption.is_some());
+
+        let (value, flags) = if is_chunked {
+            let value = encode_chunked(
+                value,
+                key,
+                self.segment_id,
+                self.compression.as_ref(),
+                self.encryption.as_ref(),
+                |raw_len, compressed_len| self.is_worth_compressing(raw_len, compressed_len),
+            )?;
+
+            (Cow::Owned(value), BLOB_FLAG_CHUNKED)
+        } else {
+            let (value, flags) = match &self.compression {
+                Some(compressor) => {
+                    let compressed = compressor.compress(value)?;
+
+                    // NOTE: Incompressible blobs are stored raw,
+                    // so reading them does not require decompression
+                    if self.is_worth_compressing(value.len(), compressed.len()) {
+                        (Cow::Owned(compressed), BLOB_FLAG_COMPRESSED)
+                    } else {
+                        (Cow::Borrowed(value), 0)
+                    }
+                }
+                None => (Cow::Borrowed(value), 0),
+            };
+
+            let value = match &self.encryption {
+                Some((encryptor, key_id)) => {
+                    let associated_data = associated_data(key, self.segment_id, None);
+                    Cow::Owned(encryptor.encrypt(*key_id, &value, &associated_data)?)
+                }
+                None => value,
+            };
+
+            (value, flags)
+        };
+
+        let Ok(value_len) = u32::try_from(value.len()) else {
+            return Err(crate::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "encoded value is larger than 2^32 bytes",
+            )));
+        };
 
-        let value = match &self.compression {
-            Some(compressor) => compressor.compress(value)?,
-            None => value.to_vec(),
+        let flags = if expires_at.is_some() {
+            flags | BLOB_FLAG_EXPIRES
+        } else {
+            flags
         };
 
-        let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+        self.max_expiry = self.max_expiry.zip(expires_at).map(|(a, b)| a.max(b));
+
+        if let Some(expires_at) = expires_at {
+            self.expiry_histogram.insert(expires_at, raw_len);
+        }
+
+        let mut hasher = blob_hasher(flags, expires_at);
         hasher.update(key);
-        hasher.update(&value);
-        let checksum = hasher.digest();
+
+        let checksum = if is_chunked {
+            chunked::checksum(hasher, &value)
+        } else {
+            hasher.update(&value);
+            hasher.digest()
+        };
 
         // TODO: 2.0.0 formalize blob header
         // into struct... store uncompressed len as well
@@ -119,7 +303,12 @@ impl<C: Compressor + Clone> Writer<C> {
         // repeated compression & decompression
 
         // Write header
-        self.active_writer.write_all(BLOB_HEADER_MAGIC)?;
+        self.active_writer.write_all(BLOB_HEADER_MAGIC_V2)?;
+        self.active_writer.write_u8(flags)?;
+
+        if let Some(expires_at) = expires_at {
+            self.active_writer.write_u64::<BigEndian>(expires_at)?;
+        }
 
         // Write checksum
         self.active_writer.write_u64::<BigEndian>(checksum)?;
@@ -134,14 +323,16 @@ impl<C: Compressor + Clone> Writer<C> {
 
         // Write value
 
-        // NOTE: Truncation is okay and actually needed
-        #[allow(clippy::cast_possible_truncation)]
-        self.active_writer
-            .write_u32::<BigEndian>(value.len() as u32)?;
+        self.active_writer.write_u32::<BigEndian>(value_len)?;
         self.active_writer.write_all(&value)?;
 
         // Header
-        self.offset += BLOB_HEADER_MAGIC.len() as u64;
+        self.offset += BLOB_HEADER_MAGIC_V2.len() as u64;
+        self.offset += std::mem::size_of::<u8>() as u64;
+
+        if expires_at.is_some() {
+            self.offset += std::mem::size_of::<u64>() as u64;
+        }
 
         // Checksum
         self.offset += std::mem::size_of::<u64>() as u64;
@@ -158,16 +349,211 @@ impl<C: Compressor + Clone> Writer<C> {
         self.written_blob_bytes += value.len() as u64;
         self.item_count += 1;
 
-        // NOTE: Truncation is okay
-        #[allow(clippy::cast_possible_truncation)]
-        Ok(value.len() as u32)
+        Ok(value_len)
     }
 
-    pub(crate) fn flush(&mut self) -> crate::Result<()> {
-        let metadata_ptr = self.active_writer.stream_position()?;
+    /// Writes an item into the file, copying the value from a reader
+    ///
+    /// Values that are not larger than the large blob threshold are read into memory
+    /// and written like [`Writer::write`]. Larger values are copied in chunks, so memory
+    /// usage is bounded. If compression or encryption is used, the value is stored in
+    /// the chunked layout, otherwise it is stored raw.
+    ///
+    /// If the write fails, the partially written blob is discarded.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the reader ends before `len` bytes are read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key length is empty or greater than 2^16.
+    pub(crate) fn write_stream<R: Read>(
+        &mut self,
+        key: &[u8],
+        reader: &mut R,
+        len: u32,
+        expires_at: Option<u64>,
+    ) -> crate::Result<u32> {
+        if u64::from(len) <= self.large_blob_threshold {
+            let mut value = vec![0; len as usize];
+            reader.read_exact(&mut value)?;
+            return self.write_with_expiry(key, &value, expires_at);
+        }
 
-        // Write metadata
-        let metadata = Metadata {
+        let result = self.write_stream_inner(key, reader, len, expires_at);
+
+        if result.is_err() {
+            // IMPORTANT: Discard the partially written blob,
+            // so the next blob is written at the expected offset
+            self.active_writer.seek(SeekFrom::Start(self.offset))?;
+            self.active_writer.get_ref().set_len(self.offset)?;
+        }
+
+        result
+    }
+
+    fn write_stream_inner<R: Read>(
+        &mut self,
+        key: &[u8],
+        reader: &mut R,
+        len: u32,
+        expires_at: Option<u64>,
+    ) -> crate::Result<u32> {
+        assert!(!key.is_empty());
+        assert!(key.len() <= u16::MAX.into());
+
+        let is_chunked = self.compression.is_some() || self.encryption.is_some();
+
+        let mut flags = if is_chunked { BLOB_FLAG_CHUNKED } else { 0 };
+
+        if expires_at.is_some() {
+            flags |= BLOB_FLAG_EXPIRES;
+        }
+
+        // Write header
+        self.active_writer.write_all(BLOB_HEADER_MAGIC_V2)?;
+        self.active_writer.write_u8(flags)?;
+
+        if let Some(expires_at) = expires_at {
+            self.active_writer.write_u64::<BigEndian>(expires_at)?;
+        }
+
+        // NOTE: The checksum is patched in after the value has been written
+        let checksum_pos = self.active_writer.stream_position()?;
+        self.active_writer.write_u64::<BigEndian>(0)?;
+
+        // Write key
+
+        // NOTE: Truncation is okay and actually needed
+        #[allow(clippy::cast_possible_truncation)]
+        self.active_writer
+            .write_u16::<BigEndian>(key.len() as u16)?;
+        self.active_writer.write_all(key)?;
+
+        // Write value
+
+        // NOTE: The length of chunked values is patched in after the value has been written
+        let value_len_pos = self.active_writer.stream_position()?;
+        self.active_writer.write_u32::<BigEndian>(len)?;
+
+        let mut hasher = blob_hasher(flags, expires_at);
+        hasher.update(key);
+
+        let mut buf = vec![0; BLOB_CHUNK_SIZE as usize];
+
+        let value_len = if is_chunked {
+            let chunk_count = len.div_ceil(BLOB_CHUNK_SIZE) as usize;
+
+            let mut table = ChunkTable {
+                chunk_size: BLOB_CHUNK_SIZE,
+                value_len: len,
+                chunks: Vec::with_capacity(chunk_count),
+            };
+
+            // NOTE: The table is patched in after the chunks have been written
+            let table_len = chunked::table_len(chunk_count);
+            self.active_writer.write_all(&vec![0; table_len])?;
+
+            let mut data_len = 0u64;
+            let mut remaining = len as usize;
+
+            while remaining > 0 {
+                let chunk_len = remaining.min(buf.len());
+
+                #[allow(clippy::indexing_slicing)]
+                let chunk = &mut buf[..chunk_len];
+                reader.read_exact(chunk)?;
+
+                // NOTE: Chunk count is limited by the value length (u32)
+                #[allow(clippy::cast_possible_truncation)]
+                let (stored, entry) = encode_chunk(
+                    chunk,
+                    table.chunks.len() as u32,
+                    key,
+                    self.segment_id,
+                    self.compression.as_ref(),
+                    self.encryption.as_ref(),
+                    |raw_len, compressed_len| self.is_worth_compressing(raw_len, compressed_len),
+                )?;
+
+                self.active_writer.write_all(&stored)?;
+                hasher.update(&stored);
+
+                data_len += stored.len() as u64;
+                remaining -= chunk_len;
+                table.chunks.push(entry);
+            }
+
+            let mut table_bytes = Vec::with_capacity(table_len);
+            table.encode_into(&mut table_bytes)?;
+            hasher.update(&table_bytes);
+
+            let Ok(value_len) = u32::try_from(table_bytes.len() as u64 + data_len) else {
+                return Err(crate::Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "encoded value is larger than 2^32 bytes",
+                )));
+            };
+
+            self.active_writer.seek(SeekFrom::Start(value_len_pos))?;
+            self.active_writer.write_u32::<BigEndian>(value_len)?;
+            self.active_writer.write_all(&table_bytes)?;
+
+            value_len
+        } else {
+            let mut remaining = len as usize;
+
+            while remaining > 0 {
+                let chunk_len = remaining.min(buf.len());
+
+                #[allow(clippy::indexing_slicing)]
+                let chunk = &mut buf[..chunk_len];
+                reader.read_exact(chunk)?;
+
+                self.active_writer.write_all(chunk)?;
+                hasher.update(chunk);
+
+                remaining -= chunk_len;
+            }
+
+            len
+        };
+
+        self.active_writer.seek(SeekFrom::Start(checksum_pos))?;
+        self.active_writer.write_u64::<BigEndian>(hasher.digest())?;
+
+        let blob_end = value_len_pos + std::mem::size_of::<u32>() as u64 + u64::from(value_len);
+        self.active_writer.seek(SeekFrom::Start(blob_end))?;
+
+        // Update metadata
+        if self.first_key.is_none() {
+            self.first_key = Some(key.into());
+        }
+        self.last_key = Some(key.into());
+
+        self.offset = blob_end;
+        self.uncompressed_bytes += u64::from(len);
+        self.written_blob_bytes += u64::from(value_len);
+        self.item_count += 1;
+
+        self.max_expiry = self.max_expiry.zip(expires_at).map(|(a, b)| a.max(b));
+
+        if let Some(expires_at) = expires_at {
+            self.expiry_histogram.insert(expires_at, len.into());
+        }
+
+        Ok(value_len)
+    }
+
+    /// Returns the metadata of the written segment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no item has been written.
+    pub(crate) fn metadata(&self) -> Metadata {
+        Metadata {
+            version: Version::V2,
             item_count: self.item_count,
             compressed_bytes: self.written_blob_bytes,
             total_uncompressed_bytes: self.uncompressed_bytes,
@@ -179,7 +565,19 @@ impl<C: Compressor + Clone> Writer<C> {
                     .clone()
                     .expect("should have written at least 1 item"),
             )),
-        };
+            compression: self.compression.as_ref().map(Compressor::compression_type),
+            encryption_key_id: self.encryption.as_ref().map(|(_, key_id)| *key_id),
+            created_at: self.created_at,
+            max_expiry: self.max_expiry,
+            expiry_histogram: self.expiry_histogram.clone(),
+        }
+    }
+
+    pub(crate) fn flush(&mut self) -> crate::Result<()> {
+        let metadata_ptr = self.active_writer.stream_position()?;
+
+        // Write metadata
+        let metadata = self.metadata();
         metadata.encode_into(&mut self.active_writer)?;
 
         SegmentFileTrailer {
@@ -194,3 +592,80 @@ impl<C: Compressor + Clone> Writer<C> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[derive(Clone)]
+    struct HalvingCompressor;
+
+    impl Compressor for HalvingCompressor {
+        fn compress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+            Ok(bytes.iter().step_by(2).copied().collect())
+        }
+
+        fn decompress(&self, bytes: &[u8]) -> crate::Result<Vec<u8>> {
+            Ok(bytes.iter().flat_map(|&x| [x, x]).collect())
+        }
+    }
+
+    /// Writes the items using [`Writer::write`] and [`Writer::write_stream`],
+    /// returning the contents of both segment files.
+    fn write_both(
+        compression: Option<HalvingCompressor>,
+        items: &[(&[u8], &[u8], Option<u64>)],
+    ) -> crate::Result<(Vec<u8>, Vec<u8>)> {
+        let folder = tempfile::tempdir()?;
+
+        let mut buffered = Writer::new(folder.path().join("0"), 0)?
+            .use_created_at(0)
+            .use_compression(compression.clone())
+            .use_large_blob_threshold(100_000);
+
+        let mut streamed = Writer::new(folder.path().join("1"), 1)?
+            .use_created_at(0)
+            .use_compression(compression)
+            .use_large_blob_threshold(100_000);
+
+        for (key, value, expires_at) in items {
+            let a = buffered.write_with_expiry(key, value, *expires_at)?;
+
+            #[allow(clippy::cast_possible_truncation)]
+            let b = streamed.write_stream(key, &mut &**value, value.len() as u32, *expires_at)?;
+
+            assert_eq!(a, b);
+            assert_eq!(buffered.offset(), streamed.offset());
+        }
+
+        buffered.flush()?;
+        streamed.flush()?;
+
+        Ok((
+            std::fs::read(folder.path().join("0"))?,
+            std::fs::read(folder.path().join("1"))?,
+        ))
+    }
+
+    #[test]
+    fn write_stream_same_as_write() -> crate::Result<()> {
+        let large = (0..150_000u32)
+            .flat_map(|x| [(x / 7) as u8, (x / 7) as u8])
+            .collect::<Vec<_>>();
+
+        let items: &[(&[u8], &[u8], Option<u64>)] = &[
+            (b"a", b"small", None),
+            (b"b", &large, None),
+            (b"c", &large, Some(1_000)),
+        ];
+
+        let (buffered, streamed) = write_both(None, items)?;
+        assert_eq!(buffered, streamed);
+
+        let (buffered, streamed) = write_both(Some(HalvingCompressor), items)?;
+        assert_eq!(buffered, streamed);
+
+        Ok(())
+    }
+}
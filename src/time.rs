@@ -0,0 +1,19 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use std::time::SystemTime;
+
+/// Converts a point in time to a unix timestamp in milliseconds
+///
+/// Times before the unix epoch are clamped to 0.
+pub fn to_unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|x| u64::try_from(x.as_millis()).unwrap_or(u64::MAX))
+        .unwrap_or_default()
+}
+
+/// Returns the current unix timestamp in milliseconds
+pub fn unix_timestamp_millis() -> u64 {
+    to_unix_millis(SystemTime::now())
+}
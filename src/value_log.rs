@@ -4,24 +4,45 @@
 
 use crate::{
     blob_cache::BlobCache,
-    gc::report::GcReport,
+    blob_reader::{BlobReader, ChunkedBlob, RawBlob},
+    config::ChecksumVerification,
+    descriptor_table::DescriptorTable,
+    encryption::EncryptorRef,
+    gc::{plan::GcPlan, report::GcReport, worker::GcWorker},
     id::{IdGenerator, SegmentId},
     index::Writer as IndexWriter,
     manifest::{SegmentManifest, SEGMENTS_FOLDER, VLOG_MARKER},
     path::absolute_path,
+    positional_reader::PositionalReader,
     scanner::{Scanner, SizeMap},
-    segment::merge::MergeReader,
-    value::UserValue,
+    segment::{chunked::ChunkTable, merge::MergeReader, reader::BlobHeader},
+    time::unix_timestamp_millis,
+    value::{UserKey, UserValue},
     version::Version,
-    Compressor, Config, GcStrategy, IndexReader, SegmentReader, SegmentWriter, ValueHandle,
+    Compressor, Config, GcStrategy, IndexReader, Segment, SegmentReader, SegmentWriter,
+    ValueHandle,
 };
 use std::{
-    fs::File,
-    io::{BufReader, Seek},
+    collections::BTreeMap,
+    io::{BufReader, Seek, SeekFrom},
     path::PathBuf,
     sync::{atomic::AtomicU64, Arc, Mutex},
 };
 
+/// Maximum amount of blobs that are looked up in the index at once during rollover
+const ROLLOVER_BATCH_SIZE: usize = 256;
+
+/// Maximum amount of value bytes that are buffered for an index lookup during rollover
+const ROLLOVER_BATCH_BYTES: usize = 4 * 1_024 * 1_024;
+
+/// Blob that is buffered for an index lookup during rollover
+type RolloverItem = (UserKey, UserValue, ValueHandle, Option<u64>);
+
+/// Returns `true` if a blob with the given expiry timestamp has expired.
+fn is_expired(expires_at: Option<u64>) -> bool {
+    expires_at.is_some_and(|x| x <= unix_timestamp_millis())
+}
+
 /// Unique value log ID
 #[allow(clippy::module_name_repetitions)]
 pub type ValueLogId = u64;
@@ -34,7 +55,7 @@ pub fn get_next_vlog_id() -> ValueLogId {
 
 /// A disk-resident value log
 #[derive(Clone)]
-pub struct ValueLog<C: Compressor + Clone>(Arc<ValueLogInner<C>>);
+pub struct ValueLog<C: Compressor + Clone>(pub(crate) Arc<ValueLogInner<C>>);
 
 impl<C: Compressor + Clone> std::ops::Deref for ValueLog<C> {
     type Target = ValueLogInner<C>;
@@ -53,11 +74,14 @@ pub struct ValueLogInner<C: Compressor + Clone> {
     pub path: PathBuf,
 
     /// Value log configuration
-    config: Config<C>,
+    pub(crate) config: Config<C>,
 
     /// In-memory blob cache
     blob_cache: Arc<BlobCache>,
 
+    /// Cache of open segment files
+    descriptor_table: Arc<DescriptorTable>,
+
     /// Segment manifest
     #[doc(hidden)]
     pub manifest: SegmentManifest<C>,
@@ -69,6 +93,27 @@ pub struct ValueLogInner<C: Compressor + Clone> {
     /// allow one to happen at a time
     #[doc(hidden)]
     pub rollover_guard: Mutex<()>,
+
+    /// Background GC worker, if started
+    pub(crate) gc_worker: Mutex<Option<GcWorker>>,
+}
+
+impl<C: Compressor + Clone> Drop for ValueLogInner<C> {
+    fn drop(&mut self) {
+        if let Ok(worker) = self.gc_worker.get_mut() {
+            if let Some(worker) = worker.take() {
+                worker.shutdown();
+            }
+        }
+
+        // NOTE: The descriptor table may be shared with other value logs,
+        // and would otherwise keep the files of this value log open
+        if let Ok(segments) = self.manifest.segments.read() {
+            for &segment_id in segments.keys() {
+                self.descriptor_table.remove(self.id, segment_id);
+            }
+        }
+    }
 }
 
 impl<C: Compressor + Clone> ValueLog<C> {
@@ -116,15 +161,13 @@ impl<C: Compressor + Clone> ValueLog<C> {
 
         let mut sum = 0;
 
-        for item in self.get_reader()? {
-            let (k, v, _, expected_checksum) = item?;
-
-            let mut hasher = xxhash_rust::xxh3::Xxh3::new();
-            hasher.update(&k);
-            hasher.update(&v);
-
-            if hasher.digest() != expected_checksum {
-                sum += 1;
+        for segment in self.manifest.list_segments() {
+            for item in segment.scan()?.verify_checksums(true) {
+                match item {
+                    Ok(_) => {}
+                    Err(crate::Error::ChecksumMismatch { .. }) => sum += 1,
+                    Err(e) => return Err(e),
+                }
             }
         }
 
@@ -132,7 +175,10 @@ impl<C: Compressor + Clone> ValueLog<C> {
     }
 
     /// Creates a new empty value log in a directory.
-    pub(crate) fn create_new<P: Into<PathBuf>>(path: P, config: Config<C>) -> crate::Result<Self> {
+    pub(crate) fn create_new<P: Into<PathBuf>>(
+        path: P,
+        mut config: Config<C>,
+    ) -> crate::Result<Self> {
         let path = absolute_path(path.into());
         log::trace!("Creating value-log at {}", path.display());
 
@@ -147,7 +193,7 @@ impl<C: Compressor + Clone> ValueLog<C> {
         // -> the V-log is fully initialized
 
         let mut file = std::fs::File::create(marker_path)?;
-        Version::V1.write_file_header(&mut file)?;
+        Version::V2.write_file_header(&mut file)?;
         file.sync_all()?;
 
         #[cfg(not(target_os = "windows"))]
@@ -161,7 +207,10 @@ impl<C: Compressor + Clone> ValueLog<C> {
             folder.sync_all()?;
         }
 
+        config.compression.load_state(&path)?;
+
         let blob_cache = config.blob_cache.clone();
+        let descriptor_table = config.descriptor_table.clone();
         let manifest = SegmentManifest::create_new(&path)?;
 
         Ok(Self(Arc::new(ValueLogInner {
@@ -169,30 +218,42 @@ impl<C: Compressor + Clone> ValueLog<C> {
             config,
             path,
             blob_cache,
+            descriptor_table,
             manifest,
             id_generator: IdGenerator::default(),
             rollover_guard: Mutex::new(()),
+            gc_worker: Mutex::new(None),
         })))
     }
 
-    pub(crate) fn recover<P: Into<PathBuf>>(path: P, config: Config<C>) -> crate::Result<Self> {
+    pub(crate) fn recover<P: Into<PathBuf>>(path: P, mut config: Config<C>) -> crate::Result<Self> {
         let path = path.into();
         log::info!("Recovering vLog at {}", path.display());
 
         {
             let bytes = std::fs::read(path.join(VLOG_MARKER))?;
 
-            if let Some(version) = Version::parse_file_header(&bytes) {
-                if version != Version::V1 {
-                    return Err(crate::Error::InvalidVersion(Some(version)));
-                }
-            } else {
+            // NOTE: V1 value logs may contain V2 segments, as segments
+            // describe their own format
+            if Version::parse_file_header(&bytes).is_none() {
                 return Err(crate::Error::InvalidVersion(None));
             }
         }
 
+        config.compression.load_state(&path)?;
+
         let blob_cache = config.blob_cache.clone();
-        let manifest = SegmentManifest::recover(&path)?;
+        let descriptor_table = config.descriptor_table.clone();
+        let manifest = SegmentManifest::recover(&path, &config.compression)?;
+
+        if config.encryption.is_none()
+            && manifest
+                .list_segments()
+                .iter()
+                .any(|x| x.meta.encryption_key_id.is_some())
+        {
+            return Err(crate::Error::MissingEncryptor);
+        }
 
         let highest_id = manifest
             .segments
@@ -208,9 +269,11 @@ impl<C: Compressor + Clone> ValueLog<C> {
             config,
             path,
             blob_cache,
+            descriptor_table,
             manifest,
             id_generator: IdGenerator::new(highest_id + 1),
             rollover_guard: Mutex::new(()),
+            gc_worker: Mutex::new(None),
         })))
     }
 
@@ -225,6 +288,16 @@ impl<C: Compressor + Clone> ValueLog<C> {
         Ok(())
     }
 
+    /// Returns the background GC worker, if it was started.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[must_use]
+    pub fn gc_worker(&self) -> Option<GcWorker> {
+        self.gc_worker.lock().expect("lock is poisoned").clone()
+    }
+
     /// Returns the amount of segments in the value log.
     #[must_use]
     pub fn segment_count(&self) -> usize {
@@ -235,41 +308,365 @@ impl<C: Compressor + Clone> ValueLog<C> {
     ///
     /// # Errors
     ///
-    /// Will return `Err` if an IO error occurs.
+    /// Will return `Err` if an IO error occurs, or the blob's checksum
+    /// does not match (see [`Config::checksum_verification`]).
     pub fn get(&self, vhandle: &ValueHandle) -> crate::Result<Option<UserValue>> {
         self.get_with_prefetch(vhandle, 0)
     }
 
+    /// Resolves a value handle, checking that the blob belongs to the given key.
+    ///
+    /// Use this to guard against value handles that have been handed out
+    /// for a different key, e.g. because of index corruption.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the blob's checksum
+    /// does not match (see [`Config::checksum_verification`]).
+    ///
+    /// Returns [`Error::KeyMismatch`](crate::Error::KeyMismatch) if the key
+    /// stored in the blob is not equal to `key`.
+    pub fn get_checked(
+        &self,
+        key: &[u8],
+        vhandle: &ValueHandle,
+    ) -> crate::Result<Option<UserValue>> {
+        let Some((stored_key, value)) = self.get_blob(vhandle, 0)? else {
+            return Ok(None);
+        };
+
+        if &*stored_key != key {
+            return Err(crate::Error::KeyMismatch {
+                segment_id: vhandle.segment_id,
+                offset: vhandle.offset,
+            });
+        }
+
+        Ok(Some(value))
+    }
+
     /// Resolves a value handle, and prefetches some values after it.
     ///
     /// # Errors
     ///
-    /// Will return `Err` if an IO error occurs.
+    /// Will return `Err` if an IO error occurs, or the blob's checksum
+    /// does not match (see [`Config::checksum_verification`]).
     pub fn get_with_prefetch(
         &self,
         vhandle: &ValueHandle,
         prefetch_size: usize,
     ) -> crate::Result<Option<UserValue>> {
-        if let Some(value) = self.blob_cache.get(self.id, vhandle) {
-            return Ok(Some(value));
+        self.get_blob(vhandle, prefetch_size)
+            .map(|item| item.map(|(_, value)| value))
+    }
+
+    /// Opens a reader that streams the value of a blob.
+    ///
+    /// Values that are stored raw are read directly from the segment file,
+    /// and large compressed or encrypted values are decoded one chunk at a time
+    /// (see [`Config::large_blob_threshold`]), so they are never held in memory entirely.
+    /// Other values are read into memory.
+    ///
+    /// The blob cache is bypassed.
+    ///
+    /// Raw values are only verified against their checksum if they are read from start
+    /// to end without seeking (see [`Config::checksum_verification`]).
+    /// Chunks are verified individually while they are read, after the chunk table
+    /// has been verified against its own checksum when opening the reader.
+    /// If the chunks are read in order, the blob checksum is verified as well.
+    ///
+    /// Returns `None` if the blob does not exist or has expired.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the chunk table's checksum does not match.
+    pub fn open_blob(&self, vhandle: &ValueHandle) -> crate::Result<Option<BlobReader<C>>> {
+        let Some(segment) = self.manifest.get_segment(vhandle.segment_id) else {
+            return Ok(None);
+        };
+
+        let file = self
+            .descriptor_table
+            .access(self.id, segment.id, &segment.path)?;
+
+        let mut reader = BufReader::new(PositionalReader::new(file));
+        reader.seek(SeekFrom::Start(vhandle.offset))?;
+
+        let Some(header) = BlobHeader::read_from(&mut reader)? else {
+            return Ok(None);
+        };
+
+        let verify_checksums = self.config.checksum_verification != ChecksumVerification::Never;
+
+        if is_expired(header.expires_at) {
+            // NOTE: The expiry timestamp is covered by the blob checksum,
+            // so make sure it is intact before hiding the value
+            if verify_checksums {
+                let mut reader = self.get_segment_reader(&segment)?;
+                reader.seek_to(vhandle.offset)?;
+
+                if let Some(item) = reader.next() {
+                    item?;
+                }
+            }
+
+            return Ok(None);
+        }
+        let encryption = self.get_segment_decryption(&segment);
+
+        if header.is_chunked {
+            let (table, expected) = ChunkTable::decode_with_checksum(&mut reader)?;
+            let data_offset = reader.stream_position()?;
+
+            // IMPORTANT: The chunk lengths are read from disk, and need to stay
+            // within the blob before chunk buffers are allocated
+            table.check_stored_len(header.value_len.into())?;
+
+            // IMPORTANT: Chunks are only checked against the table,
+            // so the table itself needs to be checked before it is trusted
+            if verify_checksums {
+                let got = table.checksum()?;
+
+                if got != expected {
+                    return Err(crate::Error::ChecksumMismatch {
+                        segment_id: segment.id,
+                        offset: vhandle.offset,
+                        expected,
+                        got,
+                    });
+                }
+            }
+
+            let hasher = verify_checksums.then(|| header.hasher());
+
+            let blob = ChunkedBlob {
+                reader: reader.into_inner(),
+                segment_id: segment.id,
+                key: header.key,
+                data_offset,
+                chunk_offsets: table.chunk_offsets(),
+                table,
+                pos: 0,
+                current: None,
+                compression: segment.decompressor.clone(),
+                encryption,
+                verify_checksums,
+                blob_offset: vhandle.offset,
+                hasher,
+                next_hashed_chunk: 0,
+                checksum: header.checksum,
+            };
+
+            return Ok(Some(blob.into()));
+        }
+
+        let is_raw =
+            encryption.is_none() && !(header.is_compressed && segment.decompressor.is_some());
+
+        if is_raw {
+            let value_offset = reader.stream_position()?;
+
+            let hasher = verify_checksums.then(|| header.hasher());
+
+            return Ok(Some(
+                RawBlob {
+                    reader: reader.into_inner(),
+                    segment_id: segment.id,
+                    blob_offset: vhandle.offset,
+                    value_offset,
+                    len: header.value_len.into(),
+                    pos: 0,
+                    hasher,
+                    checksum: header.checksum,
+                }
+                .into(),
+            ));
+        }
+
+        // NOTE: Small compressed or encrypted values need to be decoded entirely
+        let mut reader = self.get_segment_reader(&segment)?;
+        reader.seek_to(vhandle.offset)?;
+
+        let Some(item) = reader.next() else {
+            return Ok(None);
+        };
+        let (_, value, _, _) = item?;
+
+        Ok(Some(value.into()))
+    }
+
+    /// Resolves multiple value handles.
+    ///
+    /// Blobs that are not cached are grouped by segment, and each segment
+    /// is read once in offset order, so resolving many handles into the same
+    /// segment does not open and seek the segment file for every handle.
+    ///
+    /// The results are returned in the same order as the given handles.
+    /// Each result may fail individually, see [`ValueLog::get`].
+    #[must_use]
+    pub fn multi_get(&self, vhandles: &[ValueHandle]) -> Vec<crate::Result<Option<UserValue>>> {
+        let use_cache = self.config.checksum_verification != ChecksumVerification::Always;
+
+        let mut results = vhandles.iter().map(|_| None).collect::<Vec<_>>();
+
+        // Segment ID -> [(offset, result index)]
+        let mut misses: BTreeMap<SegmentId, Vec<(u64, usize)>> = BTreeMap::new();
+
+        for (idx, vhandle) in vhandles.iter().enumerate() {
+            let cached = use_cache
+                .then(|| self.blob_cache.get(self.id, vhandle))
+                .flatten();
+
+            if let (Some((_, value)), Some(result)) = (cached, results.get_mut(idx)) {
+                *result = Some(Ok(Some(value)));
+            } else {
+                misses
+                    .entry(vhandle.segment_id)
+                    .or_default()
+                    .push((vhandle.offset, idx));
+            }
+        }
+
+        for (segment_id, mut handles) in misses {
+            handles.sort_unstable();
+
+            let reader = self
+                .manifest
+                .get_segment(segment_id)
+                .map(|segment| self.get_segment_reader(&segment))
+                .transpose();
+
+            let mut reader = match reader {
+                Ok(Some(reader)) => reader,
+
+                // NOTE: Unresolved handles are returned as `None` below
+                Ok(None) => continue,
+
+                Err(e) => {
+                    // NOTE: Every handle into the segment gets the error,
+                    // so we need to duplicate it
+                    for (_, idx) in handles {
+                        if let Some(result) = results.get_mut(idx) {
+                            *result = Some(Err(crate::Error::Io(std::io::Error::new(
+                                e.kind(),
+                                e.to_string(),
+                            ))));
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            for (offset, idx) in handles {
+                let value = reader
+                    .seek_to(offset)
+                    .map_err(crate::Error::from)
+                    .and_then(|()| reader.next().transpose())
+                    .map(|item| {
+                        item.and_then(|(key, value, _checksum, expires_at)| {
+                            if is_expired(expires_at) {
+                                return None;
+                            }
+
+                            // NOTE: Expiring blobs are not cached, so they cannot
+                            // be served from the cache after expiring
+                            if use_cache && expires_at.is_none() && !self.is_large(&value) {
+                                let vhandle = ValueHandle { segment_id, offset };
+
+                                self.blob_cache
+                                    .insert((self.id, vhandle).into(), (key, value.clone()));
+                            }
+
+                            Some(value)
+                        })
+                    });
+
+                if let Some(result) = results.get_mut(idx) {
+                    *result = Some(value);
+                }
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or(Ok(None)))
+            .collect()
+    }
+
+    /// Opens a reader for the given segment, set up for point reads.
+    fn get_segment_reader(
+        &self,
+        segment: &Segment<C>,
+    ) -> std::io::Result<SegmentReader<C, PositionalReader>> {
+        let file = self
+            .descriptor_table
+            .access(self.id, segment.id, &segment.path)?;
+
+        let reader = BufReader::new(PositionalReader::new(file));
+
+        Ok(SegmentReader::with_reader(segment.id, reader)
+            .use_compression(segment.decompressor.clone())
+            .use_encryption(self.get_segment_decryption(segment))
+            .verify_checksums(self.config.checksum_verification != ChecksumVerification::Never))
+    }
+
+    /// Returns the encryptor and key ID to decrypt the segment's blobs with.
+    fn get_segment_decryption(&self, segment: &Segment<C>) -> Option<(EncryptorRef, u32)> {
+        let key_id = segment.meta.encryption_key_id?;
+        let encryptor = self.config.encryption.clone()?;
+        Some((encryptor, key_id))
+    }
+
+    /// Returns a reader that iterates through the segment's decoded blobs.
+    pub(crate) fn scan_segment(&self, segment: &Segment<C>) -> crate::Result<SegmentReader<C>> {
+        Ok(segment
+            .scan()?
+            .use_compression(segment.decompressor.clone())
+            .use_encryption(self.get_segment_decryption(segment)))
+    }
+
+    /// Resolves a value handle, returning the stored key and value.
+    fn get_blob(
+        &self,
+        vhandle: &ValueHandle,
+        prefetch_size: usize,
+    ) -> crate::Result<Option<(UserKey, UserValue)>> {
+        // NOTE: When always verifying, the blob cache is bypassed
+        // so every read is checked against the data on disk
+        let use_cache = self.config.checksum_verification != ChecksumVerification::Always;
+
+        if use_cache {
+            if let Some(item) = self.blob_cache.get(self.id, vhandle) {
+                return Ok(Some(item));
+            }
         }
 
         let Some(segment) = self.manifest.get_segment(vhandle.segment_id) else {
             return Ok(None);
         };
 
-        let mut reader = BufReader::new(File::open(&segment.path)?);
-        reader.seek(std::io::SeekFrom::Start(vhandle.offset))?;
-        let mut reader = SegmentReader::with_reader(vhandle.segment_id, reader)
-            .use_compression(self.config.compression.clone());
+        let mut reader = self.get_segment_reader(&segment)?;
+        reader.seek_to(vhandle.offset)?;
 
         let Some(item) = reader.next() else {
             return Ok(None);
         };
-        let (_key, val, _checksum) = item?;
+        let (key, val, _checksum, expires_at) = item?;
 
-        self.blob_cache
-            .insert((self.id, vhandle.clone()).into(), val.clone());
+        if is_expired(expires_at) {
+            return Ok(None);
+        }
+
+        // NOTE: Expiring blobs are not cached, so they cannot
+        // be served from the cache after expiring
+        if !use_cache || expires_at.is_some() || self.is_large(&val) {
+            return Ok(Some((key, val)));
+        }
+
+        self.blob_cache.insert(
+            (self.id, vhandle.clone()).into(),
+            (key.clone(), val.clone()),
+        );
 
         // TODO: maybe we can look at the value size and prefetch some more values
         // without causing another I/O...
@@ -281,17 +678,27 @@ impl<C: Compressor + Clone> ValueLog<C> {
             let Some(item) = reader.next() else {
                 break;
             };
-            let (_key, val, _checksum) = item?;
+            let (key, val, _checksum, expires_at) = item?;
+
+            if expires_at.is_some() || self.is_large(&val) {
+                continue;
+            }
 
             let value_handle = ValueHandle {
                 segment_id: vhandle.segment_id,
                 offset,
             };
 
-            self.blob_cache.insert((self.id, value_handle).into(), val);
+            self.blob_cache
+                .insert((self.id, value_handle).into(), (key, val));
         }
 
-        Ok(Some(val))
+        Ok(Some((key, val)))
+    }
+
+    /// Returns `true` if the value is too large to be cached.
+    fn is_large(&self, value: &[u8]) -> bool {
+        value.len() as u64 > self.config.large_blob_threshold
     }
 
     fn get_writer_raw(&self) -> crate::Result<SegmentWriter<C>> {
@@ -300,6 +707,11 @@ impl<C: Compressor + Clone> ValueLog<C> {
             self.config.segment_size_bytes,
             self.path.join(SEGMENTS_FOLDER),
         )
+        .map(|x| {
+            x.use_min_compression_savings(self.config.min_compression_savings)
+                .use_encryption(self.config.encryption.clone())
+                .use_large_blob_threshold(self.config.large_blob_threshold)
+        })
         .map_err(Into::into)
     }
 
@@ -310,10 +722,10 @@ impl<C: Compressor + Clone> ValueLog<C> {
     /// Will return `Err` if an IO error occurs.
     pub fn get_writer(&self) -> crate::Result<SegmentWriter<C>> {
         self.get_writer_raw()
-            .map(|x| x.use_compression(self.config.compression.clone()))
+            .map(|x| x.use_compression(self.config.compression.for_new_segments()))
     }
 
-    /// Drops stale segments.
+    /// Drops stale segments, and segments in which all blobs have expired.
     ///
     /// Returns the amount of disk space (compressed data) freed.
     ///
@@ -324,13 +736,17 @@ impl<C: Compressor + Clone> ValueLog<C> {
         // IMPORTANT: Only allow 1 rollover or GC at any given time
         let _guard = self.rollover_guard.lock().expect("lock is poisoned");
 
+        let now = unix_timestamp_millis();
+
+        // NOTE: Segments in which every blob has expired can be dropped
+        // without consulting the index
         let segments = self
             .manifest
             .segments
             .read()
             .expect("lock is poisoned")
             .values()
-            .filter(|x| x.is_stale())
+            .filter(|x| x.is_stale() || x.is_expired(now))
             .cloned()
             .collect::<Vec<_>>();
 
@@ -345,6 +761,7 @@ impl<C: Compressor + Clone> ValueLog<C> {
             self.manifest.drop_segments(&ids)?;
 
             for segment in segments {
+                self.descriptor_table.remove(self.id, segment.id);
                 std::fs::remove_file(&segment.path)?;
             }
         }
@@ -357,18 +774,22 @@ impl<C: Compressor + Clone> ValueLog<C> {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
-    fn mark_as_stale(&self, ids: &[SegmentId]) {
-        // NOTE: Read-locking is fine because we are dealing with an atomic bool
-        #[allow(clippy::significant_drop_tightening)]
-        let segments = self.manifest.segments.read().expect("lock is poisoned");
+    fn mark_as_stale(&self, ids: &[SegmentId]) -> crate::Result<()> {
+        {
+            // NOTE: Read-locking is fine because we are dealing with an atomic bool
+            #[allow(clippy::significant_drop_tightening)]
+            let segments = self.manifest.segments.read().expect("lock is poisoned");
 
-        for id in ids {
-            let Some(segment) = segments.get(id) else {
-                continue;
-            };
+            for id in ids {
+                let Some(segment) = segments.get(id) else {
+                    continue;
+                };
 
-            segment.mark_as_stale();
+                segment.mark_as_stale();
+            }
         }
+
+        self.manifest.persist_gc_stats(ids)
     }
 
     // TODO: remove?
@@ -380,10 +801,21 @@ impl<C: Compressor + Clone> ValueLog<C> {
         self.manifest.space_amp()
     }
 
+    /// Applies the result of an index scan to the segments' GC statistics,
+    /// and persists them.
+    ///
+    /// Segments that were removed or marked as stale while the scan
+    /// was running are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
     #[doc(hidden)]
     #[allow(clippy::cast_precision_loss)]
-    #[must_use]
-    pub fn consume_scan_result(&self, size_map: &SizeMap) -> GcReport {
+    pub fn consume_scan_result(&self, size_map: &SizeMap) -> crate::Result<GcReport> {
+        // NOTE: Prevent segments from being rolled over or dropped while reconciling
+        let _guard = self.rollover_guard.lock().expect("lock is poisoned");
+
         let mut report = GcReport {
             path: self.path.clone(),
             segment_count: self.segment_count(),
@@ -394,8 +826,15 @@ impl<C: Compressor + Clone> ValueLog<C> {
             total_blobs: 0,
         };
 
+        let mut ids = Vec::with_capacity(size_map.len());
+
         for (&id, counter) in size_map {
-            let segment = self.manifest.get_segment(id).expect("segment should exist");
+            // NOTE: The segment may have been dropped or rewritten during the scan,
+            // so the scan result is outdated
+            let Some(segment) = self.manifest.get_segment(id) else {
+                log::trace!("Blob file #{id} was dropped during scan, skipping");
+                continue;
+            };
 
             let total_bytes = segment.meta.total_uncompressed_bytes;
             let total_items = segment.meta.item_count;
@@ -403,14 +842,24 @@ impl<C: Compressor + Clone> ValueLog<C> {
             report.total_bytes += total_bytes;
             report.total_blobs += total_items;
 
+            // NOTE: The segment was rewritten during the scan
+            if segment.is_stale() {
+                log::trace!("Blob file #{id} became stale during scan, skipping");
+
+                report.stale_segment_count += 1;
+                report.stale_bytes += total_bytes;
+                report.stale_blobs += total_items;
+                continue;
+            }
+
+            ids.push(id);
+
             if counter.item_count > 0 {
                 let used_size = counter.size;
                 let alive_item_count = counter.item_count;
 
-                let segment = self.manifest.get_segment(id).expect("segment should exist");
-
-                let stale_bytes = total_bytes - used_size;
-                let stale_items = total_items - alive_item_count;
+                let stale_bytes = total_bytes.saturating_sub(used_size);
+                let stale_items = total_items.saturating_sub(alive_item_count);
 
                 segment.gc_stats.set_stale_bytes(stale_bytes);
                 segment.gc_stats.set_stale_items(stale_items);
@@ -423,7 +872,7 @@ impl<C: Compressor + Clone> ValueLog<C> {
                 segment.meta.compressed_bytes / 1_024,
                 total_bytes / 1_024 / 1_024,
             );
-                self.mark_as_stale(&[id]);
+                segment.mark_as_stale();
 
                 report.stale_segment_count += 1;
                 report.stale_bytes += total_bytes;
@@ -431,11 +880,79 @@ impl<C: Compressor + Clone> ValueLog<C> {
             }
         }
 
-        report
+        self.manifest.persist_gc_stats(&ids)?;
+
+        Ok(report)
+    }
+
+    /// Marks a blob as stale, e.g. because it was overwritten or deleted in the index.
+    ///
+    /// `size` is the (uncompressed) value size that was stored in the index.
+    ///
+    /// The GC statistics are persisted on every call, so prefer
+    /// [`ValueLog::mark_blobs_stale`] when dropping many blobs at once.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn mark_blob_stale(&self, vhandle: &ValueHandle, size: u32) -> crate::Result<()> {
+        self.mark_blobs_stale(&[(vhandle.clone(), size)])
+    }
+
+    /// Marks blobs as stale, e.g. because they were overwritten or deleted in the index.
+    ///
+    /// This allows keeping GC statistics up to date continuously, for example by calling
+    /// it from a compaction filter, so [`ValueLog::scan_for_stats`] only needs to be run
+    /// occasionally to correct drift.
+    ///
+    /// Every blob should only be reported once. Stale counters never exceed
+    /// a segment's totals, and blobs of unknown segments are ignored.
+    ///
+    /// Because reports are not deduplicated, they never make a segment fully stale
+    /// on their own, so a segment is only dropped once a scan or rollover confirms
+    /// that none of its blobs are referenced anymore.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn mark_blobs_stale(&self, blobs: &[(ValueHandle, u32)]) -> crate::Result<()> {
+        let mut stale = BTreeMap::<SegmentId, (u64, u64)>::new();
+
+        for (vhandle, size) in blobs {
+            let entry = stale.entry(vhandle.segment_id).or_default();
+            entry.0 += 1;
+            entry.1 += u64::from(*size);
+        }
+
+        {
+            // NOTE: Read-locking is fine because we are dealing with atomics
+            #[allow(clippy::significant_drop_tightening)]
+            let segments = self.manifest.segments.read().expect("lock is poisoned");
+
+            for (id, (items, bytes)) in &stale {
+                let Some(segment) = segments.get(id) else {
+                    continue;
+                };
+
+                segment.gc_stats.add_stale(
+                    *items,
+                    *bytes,
+                    segment.meta.item_count,
+                    segment.meta.total_uncompressed_bytes,
+                );
+            }
+        }
+
+        let ids = stale.into_keys().collect::<Vec<_>>();
+        self.manifest.persist_gc_stats(&ids)
     }
 
     /// Scans the given index and collects GC statistics.
     ///
+    /// The scan does not block writers, rollovers or dropping segments.
+    /// Only the segments that exist (and are not stale) when the scan starts are considered;
+    /// segments that are created, dropped or rewritten in the meantime are skipped.
+    ///
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
@@ -443,15 +960,19 @@ impl<C: Compressor + Clone> ValueLog<C> {
         &self,
         iter: impl Iterator<Item = std::io::Result<(ValueHandle, u32)>>,
     ) -> crate::Result<GcReport> {
-        let lock_guard = self.rollover_guard.lock().expect("lock is poisoned");
+        // NOTE: Stale segments are only waiting to be dropped, so there is no need to scan them
+        let ids = self
+            .manifest
+            .list_segments()
+            .iter()
+            .filter(|x| !x.is_stale())
+            .map(|x| x.id)
+            .collect::<Vec<_>>();
 
-        let ids = self.manifest.list_segment_ids();
-        let mut scanner = Scanner::new(iter, lock_guard, &ids);
+        let mut scanner = Scanner::new(iter, &ids);
         scanner.scan()?;
         let size_map = scanner.finish();
-        let report = self.consume_scan_result(&size_map);
-
-        Ok(report)
+        self.consume_scan_result(&size_map)
     }
 
     #[doc(hidden)]
@@ -477,6 +998,76 @@ impl<C: Compressor + Clone> ValueLog<C> {
         self.rollover(&ids, index_reader, index_writer)
     }
 
+    /// Projects the effect of a GC strategy, without rewriting anything.
+    ///
+    /// The projection is computed from the GC statistics and segment metadata,
+    /// so neither the index nor the disk are accessed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn plan_gc(&self, strategy: &impl GcStrategy<C>) -> GcPlan {
+        let picked = strategy.pick(self);
+
+        let segments = self.manifest.segments.read().expect("lock is poisoned");
+
+        let mut plan = GcPlan {
+            segment_ids: Vec::with_capacity(picked.len()),
+            bytes_to_read: 0,
+            live_bytes_to_rewrite: 0,
+            stale_bytes_to_free: 0,
+            space_amp_before: 0.0,
+            space_amp_after: 0.0,
+        };
+
+        for id in picked {
+            // NOTE: Segment may have been dropped in the meantime
+            let Some(segment) = segments.get(&id) else {
+                continue;
+            };
+
+            let total_bytes = segment.meta.total_uncompressed_bytes;
+            let stale_bytes = segment.gc_stats.stale_bytes().min(total_bytes);
+
+            plan.segment_ids.push(id);
+            plan.bytes_to_read += segment.meta.compressed_bytes;
+            plan.live_bytes_to_rewrite += total_bytes - stale_bytes;
+            plan.stale_bytes_to_free += stale_bytes;
+        }
+
+        let total_bytes = segments
+            .values()
+            .map(|x| x.meta.total_uncompressed_bytes)
+            .sum::<u64>();
+
+        let stale_bytes = segments
+            .values()
+            .map(|x| x.gc_stats.stale_bytes())
+            .sum::<u64>();
+
+        drop(segments);
+
+        let space_amp = |total_bytes: u64, stale_bytes: u64| {
+            let alive_bytes = total_bytes.saturating_sub(stale_bytes);
+
+            if alive_bytes == 0 {
+                return 0.0;
+            }
+
+            total_bytes as f32 / alive_bytes as f32
+        };
+
+        plan.space_amp_before = space_amp(total_bytes, stale_bytes);
+        plan.space_amp_after = space_amp(
+            total_bytes.saturating_sub(plan.stale_bytes_to_free),
+            stale_bytes.saturating_sub(plan.stale_bytes_to_free),
+        );
+
+        plan
+    }
+
     /// Applies a GC strategy.
     ///
     /// # Errors
@@ -492,6 +1083,30 @@ impl<C: Compressor + Clone> ValueLog<C> {
         self.rollover(&segment_ids, index_reader, index_writer)
     }
 
+    /// Applies a GC strategy like [`ValueLog::apply_gc_strategy`], but partitions
+    /// the rewritten segments by the given key boundaries.
+    ///
+    /// See [`ValueLog::rollover_partitioned`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn apply_gc_strategy_partitioned<R: IndexReader, W: IndexWriter>(
+        &self,
+        strategy: &impl GcStrategy<C>,
+        partition_boundaries: &[UserKey],
+        index_reader: &R,
+        index_writer: W,
+    ) -> crate::Result<u64> {
+        let segment_ids = strategy.pick(self);
+        self.rollover_partitioned(
+            &segment_ids,
+            partition_boundaries,
+            index_reader,
+            index_writer,
+        )
+    }
+
     /// Rewrites some segments into new segment(s), blocking the caller
     /// until the operation is completely done.
     ///
@@ -505,7 +1120,33 @@ impl<C: Compressor + Clone> ValueLog<C> {
         &self,
         ids: &[u64],
         index_reader: &R,
-        mut index_writer: W,
+        index_writer: W,
+    ) -> crate::Result<u64> {
+        self.rollover_partitioned(ids, &[], index_reader, index_writer)
+    }
+
+    /// Rewrites some segments into new segment(s) like [`ValueLog::rollover`], but
+    /// additionally starts a new segment at every given partition boundary.
+    ///
+    /// Because blobs are rewritten in key order, the new segments have
+    /// non-overlapping key ranges that never span a boundary.
+    /// Segments are still rotated when they reach the configured segment size.
+    ///
+    /// Returns the amount of disk space (compressed data) freed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    pub fn rollover_partitioned<R: IndexReader, W: IndexWriter>(
+        &self,
+        ids: &[u64],
+        partition_boundaries: &[UserKey],
+        index_reader: &R,
+        index_writer: W,
     ) -> crate::Result<u64> {
         if ids.is_empty() {
             return Ok(0);
@@ -516,56 +1157,130 @@ impl<C: Compressor + Clone> ValueLog<C> {
 
         let size_before = self.manifest.disk_space_used();
 
+        self.rewrite_segments(ids, partition_boundaries, index_reader, index_writer)?;
+
+        let size_after = self.manifest.disk_space_used();
+
+        Ok(size_before.saturating_sub(size_after))
+    }
+
+    /// Rewrites some segments like [`ValueLog::rollover`], but returns
+    /// the amount of bytes (compressed data) written into new segments.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    pub(crate) fn rollover_counting_writes<R: IndexReader, W: IndexWriter>(
+        &self,
+        ids: &[u64],
+        index_reader: &R,
+        index_writer: W,
+    ) -> crate::Result<u64> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        // IMPORTANT: Only allow 1 rollover or GC at any given time
+        let _guard = self.rollover_guard.lock().expect("lock is poisoned");
+
+        self.rewrite_segments(ids, &[], index_reader, index_writer)
+    }
+
+    /// Rewrites some segments into new segment(s).
+    ///
+    /// Segments that no longer exist are skipped.
+    ///
+    /// The caller needs to hold the rollover guard.
+    ///
+    /// Returns the amount of bytes (compressed data) written.
+    pub(crate) fn rewrite_segments<R: IndexReader, W: IndexWriter>(
+        &self,
+        ids: &[u64],
+        partition_boundaries: &[UserKey],
+        index_reader: &R,
+        mut index_writer: W,
+    ) -> crate::Result<u64> {
         log::info!("Rollover segments {ids:?}");
 
         let segments = ids
             .iter()
-            .map(|&x| self.manifest.get_segment(x))
-            .collect::<Option<Vec<_>>>();
+            .filter_map(|&x| {
+                let segment = self.manifest.get_segment(x);
 
-        let Some(segments) = segments else {
+                if segment.is_none() {
+                    log::trace!("Blob file #{x} does not exist anymore, skipping");
+                }
+
+                segment
+            })
+            .collect::<Vec<_>>();
+
+        if segments.is_empty() {
             return Ok(0);
-        };
+        }
 
-        let readers = segments
-            .into_iter()
-            .map(|x| x.scan())
-            .collect::<crate::Result<Vec<_>>>()?;
+        let ids = segments.iter().map(|x| x.id).collect::<Vec<_>>();
 
         // TODO: 2.0.0: Store uncompressed size per blob
         // so we can avoid recompression costs during GC
         // but have stats be correct
 
-        let reader = MergeReader::new(
-            readers
-                .into_iter()
-                .map(|x| x.use_compression(self.config.compression.clone()))
-                .collect(),
-        );
+        // NOTE: The rewritten data is as old as the youngest segment it comes from
+        let created_at = segments
+            .iter()
+            .map(|x| x.created_at())
+            .max()
+            .unwrap_or_default();
+
+        // NOTE: Blobs are checked against their checksum, so a corrupted
+        // expiry timestamp cannot make rollover drop a live blob
+        let readers = segments
+            .into_iter()
+            .map(|x| {
+                self.scan_segment(&x).map(|reader| {
+                    reader.verify_checksums(
+                        self.config.checksum_verification != ChecksumVerification::Never,
+                    )
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let reader = MergeReader::new(readers);
 
         let mut writer = self
             .get_writer_raw()?
-            .use_compression(self.config.compression.clone());
+            .use_compression(self.config.compression.for_new_segments())
+            .use_created_at(created_at)
+            .use_partition_boundaries(partition_boundaries.to_vec());
+
+        let mut batch = Vec::with_capacity(ROLLOVER_BATCH_SIZE);
+        let mut batch_bytes = 0;
 
         for item in reader {
-            let (k, v, segment_id, _) = item?;
+            let (k, v, vhandle, _, expires_at) = item?;
 
-            match index_reader.get(&k)? {
-                // If this value is in an older segment, we can discard it
-                Some(vhandle) if segment_id < vhandle.segment_id => continue,
-                None => continue,
-                _ => {}
+            // NOTE: Expired blobs are not rewritten
+            if is_expired(expires_at) {
+                continue;
             }
 
-            let vhandle = writer.get_next_value_handle();
-
-            // NOTE: Truncation is OK because we know values are u32 max
-            #[allow(clippy::cast_possible_truncation)]
-            index_writer.insert_indirect(&k, vhandle, v.len() as u32)?;
+            batch_bytes += v.len();
+            batch.push((k, v, vhandle, expires_at));
 
-            writer.write(&k, &v)?;
+            if batch.len() >= ROLLOVER_BATCH_SIZE || batch_bytes >= ROLLOVER_BATCH_BYTES {
+                Self::rewrite_batch(&mut batch, index_reader, &mut index_writer, &mut writer)?;
+                batch_bytes = 0;
+            }
         }
 
+        Self::rewrite_batch(&mut batch, index_reader, &mut index_writer, &mut writer)?;
+
+        let bytes_written = writer.written_bytes();
+
         // IMPORTANT: New segments need to be persisted before adding to index
         // to avoid dangling pointers
         self.manifest.register(writer)?;
@@ -577,10 +1292,63 @@ impl<C: Compressor + Clone> ValueLog<C> {
         // IMPORTANT: We only mark the segments as definitely stale
         // The external index needs to decide when it is safe to drop
         // the old segments, as some reads may still be performed
-        self.mark_as_stale(ids);
+        self.mark_as_stale(&ids)?;
 
-        let size_after = self.manifest.disk_space_used();
+        Ok(bytes_written)
+    }
 
-        Ok(size_before.saturating_sub(size_after))
+    /// Looks up a batch of blobs in the index, and rewrites the ones that are still referenced.
+    ///
+    /// The batch is empty afterwards.
+    fn rewrite_batch<R: IndexReader, W: IndexWriter>(
+        batch: &mut Vec<RolloverItem>,
+        index_reader: &R,
+        index_writer: &mut W,
+        writer: &mut SegmentWriter<C>,
+    ) -> crate::Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let vhandles = {
+            let keys = batch.iter().map(|(k, _, _, _)| &**k).collect::<Vec<_>>();
+            index_reader.get_many(&keys)?
+        };
+
+        // IMPORTANT: Otherwise blobs would silently not be rewritten,
+        // and be lost when the old segments are dropped
+        if vhandles.len() != batch.len() {
+            return Err(crate::Error::Io(std::io::Error::other(format!(
+                "index returned {} value handles for {} keys",
+                vhandles.len(),
+                batch.len(),
+            ))));
+        }
+
+        for ((k, v, expected, expires_at), vhandle) in batch.drain(..).zip(vhandles) {
+            // NOTE: If the index points somewhere else, this blob has been overwritten
+            // (possibly by a newer blob of the same key in the same segment), so we can discard it
+            if vhandle.as_ref() != Some(&expected) {
+                continue;
+            }
+
+            writer.enter_partition(&k)?;
+
+            let vhandle = writer.get_next_value_handle();
+
+            // IMPORTANT: The key may have been overwritten in the meantime,
+            // so only relocate it if it still points to the old blob
+            //
+            // NOTE: Truncation is OK because we know values are u32 max
+            #[allow(clippy::cast_possible_truncation)]
+            index_writer.insert_indirect_if(&k, &expected, vhandle, v.len() as u32)?;
+
+            match expires_at {
+                Some(expires_at) => writer.write_with_expiry(&k, &v, expires_at)?,
+                None => writer.write(&k, &v)?,
+            };
+        }
+
+        Ok(())
     }
 }
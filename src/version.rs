@@ -6,9 +6,15 @@ use byteorder::WriteBytesExt;
 
 /// Disk format version
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum Version {
     /// Version for 1.x.x releases
     V1,
+
+    /// Version with self-describing segments
+    ///
+    /// Segments record which compression their blobs were written with.
+    V2,
 }
 
 impl std::fmt::Display for Version {
@@ -21,6 +27,7 @@ impl From<Version> for u8 {
     fn from(value: Version) -> Self {
         match value {
             Version::V1 => 1,
+            Version::V2 => 2,
         }
     }
 }
@@ -30,6 +37,7 @@ impl TryFrom<u8> for Version {
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             1 => Ok(Self::V1),
+            2 => Ok(Self::V2),
             _ => Err(()),
         }
     }
@@ -105,6 +113,16 @@ mod tests {
         assert_eq!(version, Some(Version::V1));
     }
 
+    #[test]
+    #[allow(clippy::expect_used)]
+    pub fn version_serde_round_trip_v2() {
+        let mut buf = vec![];
+        Version::V2.write_file_header(&mut buf).expect("can't fail");
+
+        let version = Version::parse_file_header(&buf);
+        assert_eq!(version, Some(Version::V2));
+    }
+
     #[test]
     #[allow(clippy::expect_used)]
     pub fn version_len() {
@@ -52,11 +52,8 @@ fn accidental_drop_rc() -> value_log::Result<()> {
 
     // NOTE: Now start a new GC scan
     let index_lock = index.read().unwrap();
-    let mut scanner = value_log::scanner::Scanner::new(
-        index_lock.values().cloned().map(Ok),
-        value_log.rollover_guard.lock().unwrap(),
-        &segment_ids,
-    );
+    let mut scanner =
+        value_log::scanner::Scanner::new(index_lock.values().cloned().map(Ok), &segment_ids);
     scanner.scan()?;
     let scan_result = scanner.finish();
     drop(index_lock);
@@ -82,7 +79,7 @@ fn accidental_drop_rc() -> value_log::Result<()> {
     //
     // But we are forced to pass the list of segment IDs we saw before starting the
     // scan, which prevents marking ones as stale that were created later
-    let _ = value_log.consume_scan_result(&scan_result);
+    let _ = value_log.consume_scan_result(&scan_result)?;
 
     // IMPORTANT: The new blob file should not be dropped
     value_log.drop_stale_segments()?;
@@ -0,0 +1,135 @@
+#![cfg(feature = "async")]
+
+use std::sync::Arc;
+use value_log::{
+    AsyncValueLog, Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter,
+    StaleThresholdStrategy,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+async fn write_items(
+    value_log: &AsyncValueLog<NoCompressor>,
+    index: &MockIndex,
+    keys: &[&str],
+) -> value_log::Result<()> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer().await?;
+
+    for key in keys {
+        let value = key.repeat(1_000);
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+        writer.write(key, &value)?;
+    }
+
+    value_log.register_writer(writer).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_value_log_get() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let index = MockIndex::default();
+
+    let value_log = AsyncValueLog::open(folder.path(), Config::<NoCompressor>::default()).await?;
+
+    write_items(&value_log, &index, &["a", "b", "c"]).await?;
+
+    let vhandle = index.get(b"b")?.unwrap();
+    assert_eq!(
+        "b".repeat(1_000).as_bytes(),
+        &*value_log.get(&vhandle).await?.unwrap(),
+    );
+
+    let vhandles = ["c", "a"]
+        .iter()
+        .map(|key| index.get(key.as_bytes()).map(Option::unwrap))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let values = value_log.multi_get(vhandles).await?;
+    assert_eq!(2, values.len());
+    assert_eq!(
+        "c".repeat(1_000).as_bytes(),
+        &*values[0].as_ref().unwrap().clone().unwrap(),
+    );
+    assert_eq!(
+        "a".repeat(1_000).as_bytes(),
+        &*values[1].as_ref().unwrap().clone().unwrap(),
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_value_log_gc() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let index = MockIndex::default();
+
+    // NOTE: Perform I/O on a separate, small blocking pool
+    let blocking_pool = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .max_blocking_threads(1)
+        .build()?;
+
+    let value_log = AsyncValueLog::open(folder.path(), Config::<NoCompressor>::default())
+        .await?
+        .with_blocking_pool(blocking_pool.handle().clone());
+
+    write_items(&value_log, &index, &["a", "b", "c", "d"]).await?;
+    write_items(&value_log, &index, &["e", "f"]).await?;
+
+    index.remove(b"a");
+    index.remove(b"b");
+    index.remove(b"c");
+
+    let snapshot = index
+        .read()
+        .unwrap()
+        .values()
+        .cloned()
+        .map(Ok)
+        .collect::<Vec<_>>();
+
+    let report = value_log.scan_for_stats(snapshot.into_iter()).await?;
+    assert_eq!(3, report.stale_blobs);
+
+    let index_reader = Arc::new(index.clone());
+
+    value_log
+        .apply_gc_strategy(
+            &StaleThresholdStrategy::new(0.5),
+            index_reader,
+            MockIndexWriter(index.clone()),
+        )
+        .await?;
+
+    value_log.drop_stale_segments().await?;
+    assert_eq!(2, value_log.inner().segment_count());
+
+    for key in ["d", "e", "f"] {
+        let vhandle = index.get(key.as_bytes())?.unwrap();
+        assert_eq!(
+            key.repeat(1_000).as_bytes(),
+            &*value_log.get(&vhandle).await?.unwrap(),
+        );
+    }
+
+    blocking_pool.shutdown_background();
+
+    Ok(())
+}
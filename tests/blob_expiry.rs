@@ -0,0 +1,245 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[test]
+fn blob_expiry_get() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    let expired = writer.get_next_value_handle();
+    index_writer.insert_indirect(b"a", expired.clone(), 1)?;
+    writer.write_with_expiry(b"a", b"a", now() - 1)?;
+
+    let alive = writer.get_next_value_handle();
+    index_writer.insert_indirect(b"b", alive.clone(), 1)?;
+    writer.write_with_ttl(b"b", b"b", Duration::from_secs(3_600))?;
+
+    let forever = writer.get_next_value_handle();
+    index_writer.insert_indirect(b"c", forever.clone(), 1)?;
+    writer.write(b"c", b"c")?;
+
+    value_log.register_writer(writer)?;
+
+    assert_eq!(None, value_log.get(&expired)?);
+    assert_eq!(Some(b"b".into()), value_log.get(&alive)?);
+    assert_eq!(Some(b"c".into()), value_log.get(&forever)?);
+
+    let results = value_log.multi_get(&[expired, alive, forever]);
+    assert!(results.first().unwrap().as_ref().unwrap().is_none());
+    assert!(results.get(1).unwrap().as_ref().unwrap().is_some());
+    assert!(results.get(2).unwrap().as_ref().unwrap().is_some());
+
+    Ok(())
+}
+
+#[test]
+fn blob_expiry_drop_expired_segments() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        // NOTE: Every blob has expired
+        let mut writer = value_log.get_writer()?;
+        writer.write_with_expiry(b"a", b"a", now() - 1_000)?;
+        writer.write_with_expiry(b"b", b"b", now() - 1)?;
+        value_log.register_writer(writer)?;
+
+        // NOTE: Some blobs have not expired
+        let mut writer = value_log.get_writer()?;
+        writer.write_with_expiry(b"a", b"a", now() - 1)?;
+        writer.write_with_ttl(b"b", b"b", Duration::from_secs(3_600))?;
+        value_log.register_writer(writer)?;
+
+        // NOTE: Some blobs never expire
+        let mut writer = value_log.get_writer()?;
+        writer.write_with_expiry(b"a", b"a", now() - 1)?;
+        writer.write(b"b", b"b")?;
+        value_log.register_writer(writer)?;
+
+        assert_eq!(3, value_log.segment_count());
+    }
+
+    {
+        // NOTE: The max expiry is recovered from the segment metadata
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        assert_eq!(3, value_log.segment_count());
+
+        let segments = value_log.manifest.list_segments();
+        assert_eq!(1, segments.iter().filter(|x| x.is_expired(now())).count());
+        assert_eq!(
+            1,
+            segments
+                .iter()
+                .filter(|x| x.meta.max_expiry.is_none())
+                .count()
+        );
+
+        // NOTE: No index scan needed
+        value_log.drop_stale_segments()?;
+        assert_eq!(2, value_log.segment_count());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn blob_expiry_rollover() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    let expires_at = now() + 3_600_000;
+
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    for (key, expiry) in [("a", now() - 1), ("b", expires_at), ("c", expires_at)] {
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, 1)?;
+        writer.write_with_expiry(key, key, expiry)?;
+    }
+
+    value_log.register_writer(writer)?;
+
+    let ids = value_log.manifest.list_segment_ids();
+    value_log.rollover(&ids, &index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+    assert_eq!(1, value_log.segment_count());
+
+    // NOTE: The expired blob is not rewritten, the others keep their expiry
+    let segment = value_log.manifest.list_segments().pop().unwrap();
+    assert_eq!(2, segment.meta.item_count);
+    assert_eq!(Some(expires_at), segment.meta.max_expiry);
+
+    for kv in value_log.get_reader()? {
+        let (_, _, _, _, expiry) = kv?;
+        assert_eq!(Some(expires_at), expiry);
+    }
+
+    let index = index.read().unwrap();
+    let (vhandle, _) = index.get(b"b".as_slice()).unwrap();
+    assert_eq!(Some(b"b".into()), value_log.get(vhandle)?);
+
+    Ok(())
+}
+
+#[test]
+fn blob_expiry_corrupted_timestamp() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    let vhandle = writer.get_next_value_handle();
+    index_writer.insert_indirect(b"a", vhandle.clone(), 1)?;
+    writer.write_with_ttl(b"a", b"a", Duration::from_secs(3_600))?;
+
+    value_log.register_writer(writer)?;
+
+    // NOTE: Make the blob look expired, the timestamp follows the header magic and flags
+    {
+        let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+
+        let start = vhandle.offset as usize + 8 + 1;
+
+        let mut bytes = std::fs::read(&segment.path)?;
+        bytes[start..start + 8].copy_from_slice(&1u64.to_be_bytes());
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    assert!(matches!(
+        value_log.get(&vhandle),
+        Err(value_log::Error::ChecksumMismatch { .. }),
+    ));
+    assert!(matches!(
+        value_log.open_blob(&vhandle),
+        Err(value_log::Error::ChecksumMismatch { .. }),
+    ));
+
+    // NOTE: Rollover must not drop the blob as expired
+    let ids = value_log.manifest.list_segment_ids();
+    assert!(matches!(
+        value_log.rollover(&ids, &index, MockIndexWriter(index.clone())),
+        Err(value_log::Error::ChecksumMismatch { .. }),
+    ));
+    assert_eq!(Some(vhandle), index.get(b"a")?);
+    assert_eq!(1, value_log.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn blob_expiry_corrupted_segment_metadata() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let expires_at = now() + 3_600_000;
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        let mut writer = value_log.get_writer()?;
+        writer.write_with_expiry(b"a", b"a", expires_at)?;
+        value_log.register_writer(writer)?;
+
+        // NOTE: Make the segment look expired, so it would be dropped without reading its blobs
+        let segment = value_log.manifest.list_segments().pop().unwrap();
+
+        let mut bytes = std::fs::read(&segment.path)?;
+
+        let metadata_ptr = {
+            let trailer_start = bytes.len() - 256;
+            u64::from_be_bytes(bytes[trailer_start..trailer_start + 8].try_into().unwrap()) as usize
+        };
+
+        let pos = metadata_ptr
+            + bytes[metadata_ptr..]
+                .windows(8)
+                .position(|x| x == expires_at.to_be_bytes())
+                .unwrap();
+
+        bytes[pos..pos + 8].copy_from_slice(&1u64.to_be_bytes());
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    assert!(matches!(
+        ValueLog::open(vl_path, Config::<NoCompressor>::default()),
+        Err(value_log::Error::Decode(_)),
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,364 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    sync::Arc,
+};
+use test_log::test;
+use value_log::{
+    BlobCache, ChecksumVerification, Compressor, Config, IndexReader, IndexWriter, MockIndex,
+    MockIndexWriter, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+/// Compressor that transforms the data, so decompression is required to read it
+#[derive(Clone, Default)]
+struct XorCompressor;
+
+impl Compressor for XorCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.iter().map(|x| x ^ 0xAA).collect())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.iter().map(|x| x ^ 0xAA).collect())
+    }
+}
+
+fn large_value(len: usize) -> Vec<u8> {
+    (0..len).map(|x| (x % 251) as u8).collect()
+}
+
+fn write_items<C: Compressor + Clone>(
+    value_log: &ValueLog<C>,
+    index: &MockIndex,
+    items: &[(&str, &[u8])],
+) -> value_log::Result<()> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    for (key, value) in items {
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+        writer.write(key, value)?;
+    }
+
+    value_log.register_writer(writer)
+}
+
+#[test]
+fn blob_stream_raw() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let blob_cache = Arc::new(BlobCache::with_capacity_bytes(64 * 1_024 * 1_024));
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<NoCompressor>::default()
+            .blob_cache(blob_cache.clone())
+            .large_blob_threshold(100_000),
+    )?;
+
+    let large = large_value(300_000);
+    write_items(&value_log, &index, &[("large", &large), ("small", b"abc")])?;
+
+    let vhandle = index.get(b"large")?.unwrap();
+
+    let mut reader = value_log.open_blob(&vhandle)?.unwrap();
+    assert!(reader.is_streaming());
+    assert_eq!(large.len() as u64, reader.len());
+
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf)?;
+    assert_eq!(large, buf);
+
+    reader.seek(SeekFrom::Start(123_456))?;
+    let mut buf = [0; 100];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&large[123_456..123_556], buf);
+
+    reader.seek(SeekFrom::End(-10))?;
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf)?;
+    assert_eq!(&large[large.len() - 10..], buf);
+
+    // NOTE: Large blobs bypass the blob cache
+    assert_eq!(&large, &*value_log.get(&vhandle)?.unwrap());
+    assert!(blob_cache.is_empty());
+
+    let vhandle = index.get(b"small")?.unwrap();
+    assert_eq!(b"abc", &*value_log.get(&vhandle)?.unwrap());
+    assert_eq!(1, blob_cache.len());
+
+    let mut buf = vec![];
+    value_log
+        .open_blob(&vhandle)?
+        .unwrap()
+        .read_to_end(&mut buf)?;
+    assert_eq!(b"abc", &*buf);
+
+    Ok(())
+}
+
+#[test]
+fn blob_stream_chunked() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let config = || Config::<XorCompressor>::default().large_blob_threshold(100_000);
+
+    let large = large_value(300_000);
+
+    {
+        let value_log = ValueLog::open(vl_path, config())?;
+        write_items(&value_log, &index, &[("large", &large), ("small", b"abc")])?;
+    }
+
+    let value_log = ValueLog::open(vl_path, config())?;
+
+    assert_eq!(0, value_log.verify()?);
+
+    let vhandle = index.get(b"large")?.unwrap();
+    assert_eq!(&large, &*value_log.get(&vhandle)?.unwrap());
+
+    let mut reader = value_log.open_blob(&vhandle)?.unwrap();
+    assert!(reader.is_streaming());
+    assert_eq!(large.len() as u64, reader.len());
+
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf)?;
+    assert_eq!(large, buf);
+
+    // NOTE: Read across a chunk boundary
+    reader.seek(SeekFrom::Start(65_500))?;
+    let mut buf = [0; 100];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&large[65_500..65_600], buf);
+
+    reader.seek(SeekFrom::Current(-200))?;
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&large[65_400..65_500], buf);
+
+    // NOTE: Small compressed blobs are not chunked, so they are read into memory
+    let vhandle = index.get(b"small")?.unwrap();
+    let mut reader = value_log.open_blob(&vhandle)?.unwrap();
+    assert!(!reader.is_streaming());
+
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf)?;
+    assert_eq!(b"abc", &*buf);
+
+    // NOTE: Rewritten blobs stay chunked
+    value_log.major_compact(&index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+
+    let vhandle = index.get(b"large")?.unwrap();
+    let mut reader = value_log.open_blob(&vhandle)?.unwrap();
+    assert!(reader.is_streaming());
+
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf)?;
+    assert_eq!(large, buf);
+
+    assert_eq!(0, value_log.verify()?);
+
+    Ok(())
+}
+
+#[test]
+fn blob_stream_chunked_corruption() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<XorCompressor>::default().large_blob_threshold(100_000),
+    )?;
+
+    let large = large_value(300_000);
+    write_items(&value_log, &index, &[("large", &large)])?;
+
+    let vhandle = index.get(b"large")?.unwrap();
+    let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+
+    // NOTE: Corrupt the last chunk
+    {
+        // magic + flags + checksum + key len + key + value len + value
+        let blob_end = vhandle.offset as usize
+            + 8
+            + 1
+            + 8
+            + 2
+            + b"large".len()
+            + 4
+            + segment.meta.compressed_bytes as usize;
+
+        let mut bytes = std::fs::read(&segment.path)?;
+        bytes[blob_end - 1] ^= 0xFF;
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    // NOTE: Chunks are checked against the chunk table when they are read,
+    // so the first chunks can still be streamed
+    let mut reader = value_log.open_blob(&vhandle)?.unwrap();
+
+    let mut buf = vec![0; 100_000];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&large[..100_000], buf);
+
+    let err = reader.read_to_end(&mut vec![]).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+
+    assert!(matches!(
+        value_log.get(&vhandle),
+        Err(value_log::Error::ChecksumMismatch { .. }),
+    ));
+
+    assert_eq!(1, value_log.verify()?);
+
+    Ok(())
+}
+
+#[test]
+fn blob_stream_chunked_table_corruption() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<XorCompressor>::default().large_blob_threshold(100_000),
+    )?;
+
+    let large = large_value(300_000);
+    write_items(&value_log, &index, &[("large", &large)])?;
+
+    let vhandle = index.get(b"large")?.unwrap();
+    let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+
+    // NOTE: Corrupt the value length in the chunk table,
+    // which is not covered by the chunk checksums
+    {
+        // magic + flags + checksum + key len + key + value len
+        let table_start = vhandle.offset as usize + 8 + 1 + 8 + 2 + b"large".len() + 4;
+
+        let mut bytes = std::fs::read(&segment.path)?;
+        bytes[table_start + 7] ^= 0x20;
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    assert!(matches!(
+        value_log.open_blob(&vhandle),
+        Err(value_log::Error::ChecksumMismatch { .. }),
+    ));
+
+    assert_eq!(1, value_log.verify()?);
+
+    Ok(())
+}
+
+#[test]
+fn blob_stream_chunked_stored_len_corruption() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    // NOTE: The table checksum is not verified, so the stored length
+    // needs to be checked against the blob before it is used
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<XorCompressor>::default()
+            .large_blob_threshold(100_000)
+            .checksum_verification(ChecksumVerification::Never),
+    )?;
+
+    let large = large_value(300_000);
+    write_items(&value_log, &index, &[("large", &large)])?;
+
+    let vhandle = index.get(b"large")?.unwrap();
+    let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+
+    // NOTE: Corrupt the stored length of the first chunk,
+    // which follows the chunk size, value length, chunk count and chunk flags
+    {
+        // magic + flags + checksum + key len + key + value len
+        let table_start = vhandle.offset as usize + 8 + 1 + 8 + 2 + b"large".len() + 4;
+        let stored_len_start = table_start + 4 + 4 + 4 + 1;
+
+        let mut bytes = std::fs::read(&segment.path)?;
+        bytes[stored_len_start..stored_len_start + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    assert!(matches!(
+        value_log.open_blob(&vhandle),
+        Err(value_log::Error::Decode(_)),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn blob_stream_chunked_blob_checksum() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<XorCompressor>::default()
+            .large_blob_threshold(100_000)
+            .checksum_verification(ChecksumVerification::Always),
+    )?;
+
+    let large = large_value(300_000);
+    write_items(&value_log, &index, &[("large", &large)])?;
+
+    let vhandle = index.get(b"large")?.unwrap();
+    let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+
+    // NOTE: Corrupt the key, which is only covered by the blob checksum
+    {
+        // magic + flags + checksum + key len
+        let key_start = vhandle.offset as usize + 8 + 1 + 8 + 2;
+
+        let mut bytes = std::fs::read(&segment.path)?;
+        bytes[key_start] ^= 0xFF;
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    let mut reader = value_log.open_blob(&vhandle)?.unwrap();
+
+    let mut buf = vec![0; 100_000];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(&large[..100_000], buf);
+
+    // NOTE: The blob checksum is verified once the last chunk is read
+    let err = reader.read_to_end(&mut vec![]).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+
+    assert_eq!(1, value_log.verify()?);
+
+    Ok(())
+}
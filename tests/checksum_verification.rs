@@ -0,0 +1,152 @@
+use std::sync::Arc;
+use test_log::test;
+use value_log::{
+    BlobCache, ChecksumVerification, Compressor, Config, IndexWriter, MockIndex, MockIndexWriter,
+    ValueHandle, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn flip_last_byte(path: &std::path::Path, vhandle: &ValueHandle, key: &[u8], value: &[u8]) {
+    // magic + flags + checksum + key len + key + value len + value
+    // (the blobs do not expire, so there is no expiry timestamp)
+    let blob_end = vhandle.offset as usize + 8 + 1 + 8 + 2 + key.len() + 4 + value.len();
+
+    let mut bytes = std::fs::read(path).unwrap();
+    bytes[blob_end - 1] ^= 0xFF;
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn checksum_verification_on_read() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let key = b"a";
+    let value = b"a".repeat(1_000);
+
+    let vhandle = {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key, vhandle.clone(), value.len() as u32)?;
+        writer.write(key, &value)?;
+
+        value_log.register_writer(writer)?;
+
+        let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+        flip_last_byte(&segment.path, &vhandle, key, &value);
+
+        vhandle
+    };
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        match value_log.get(&vhandle) {
+            Err(value_log::Error::ChecksumMismatch {
+                segment_id,
+                offset,
+                expected,
+                got,
+            }) => {
+                assert_eq!(vhandle.segment_id, segment_id);
+                assert_eq!(vhandle.offset, offset);
+                assert_ne!(expected, got);
+            }
+            other => panic!("expected checksum mismatch, got {other:?}"),
+        }
+    }
+
+    {
+        let value_log = ValueLog::open(
+            vl_path,
+            Config::<NoCompressor>::default().checksum_verification(ChecksumVerification::Never),
+        )?;
+
+        let item = value_log.get(&vhandle)?.unwrap();
+        assert_ne!(&*item, &*value);
+        assert_eq!(item.len(), value.len());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn checksum_verification_always_bypasses_cache() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let key = b"a";
+    let value = b"a".repeat(1_000);
+
+    let blob_cache = Arc::new(BlobCache::with_capacity_bytes(1_000_000));
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<NoCompressor>::default()
+            .blob_cache(blob_cache.clone())
+            .checksum_verification(ChecksumVerification::Always),
+    )?;
+
+    let mut writer = value_log.get_writer()?;
+    let vhandle = writer.get_next_value_handle();
+    writer.write(key, &value)?;
+    value_log.register_writer(writer)?;
+
+    assert_eq!(&*value_log.get(&vhandle)?.unwrap(), &*value);
+    assert!(blob_cache.is_empty());
+
+    let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+    flip_last_byte(&segment.path, &vhandle, key, &value);
+
+    assert!(matches!(
+        value_log.get(&vhandle),
+        Err(value_log::Error::ChecksumMismatch { .. }),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn checksum_verification_on_cache_miss_uses_cache() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let key = b"a";
+    let value = b"a".repeat(1_000);
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let mut writer = value_log.get_writer()?;
+    let vhandle = writer.get_next_value_handle();
+    writer.write(key, &value)?;
+    value_log.register_writer(writer)?;
+
+    // Load blob into cache
+    assert_eq!(&*value_log.get(&vhandle)?.unwrap(), &*value);
+
+    let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+    flip_last_byte(&segment.path, &vhandle, key, &value);
+
+    // Served from cache, which was verified when it was loaded
+    assert_eq!(&*value_log.get(&vhandle)?.unwrap(), &*value);
+
+    Ok(())
+}
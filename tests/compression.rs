@@ -11,7 +11,8 @@ impl Compressor for Lz4Compressor {
     }
 
     fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
-        lz4_flex::decompress_size_prepended(bytes).map_err(|_| value_log::Error::Decompress)
+        lz4_flex::decompress_size_prepended(bytes)
+            .map_err(|e| value_log::Error::Decompress(e.into()))
     }
 }
 
@@ -0,0 +1,140 @@
+#![cfg(any(feature = "lz4", feature = "zstd"))]
+
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueLog,
+};
+
+fn roundtrip<C: Compressor + Clone + Default + Send + Sync + 'static>(
+    compressor: C,
+) -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::default().compression(compressor))?;
+
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    let items = ["a", "b", "c"];
+
+    for key in items {
+        let value = "verycompressable".repeat(1_000);
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+        let written_bytes = writer.write(key, &value)?;
+        assert!(written_bytes < value.len() as u32);
+    }
+
+    value_log.register_writer(writer)?;
+
+    for key in items {
+        let vhandle = index.get(key.as_bytes())?.unwrap();
+        let value = value_log.get(&vhandle)?.unwrap();
+        assert_eq!(&*value, "verycompressable".repeat(1_000).as_bytes());
+    }
+
+    assert_eq!(0, value_log.verify()?);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "lz4")]
+fn compression_builtin_lz4() -> value_log::Result<()> {
+    roundtrip(value_log::Lz4Compressor)
+}
+
+#[test]
+#[cfg(feature = "lz4")]
+fn compression_builtin_lz4_decompress_error() {
+    use std::error::Error;
+
+    let err = value_log::Lz4Compressor
+        .decompress(b"not lz4")
+        .expect_err("should fail");
+
+    assert!(matches!(err, value_log::Error::Decompress(_)));
+    assert!(err.source().is_some());
+}
+
+#[test]
+#[cfg(feature = "lz4")]
+fn compression_builtin_lz4_corrupted_size_prefix() {
+    let mut bytes = value_log::Lz4Compressor
+        .compress(b"abcdefgh")
+        .expect("should compress");
+
+    // NOTE: Claim the value is ~4 GiB
+    bytes[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert!(matches!(
+        value_log::Lz4Compressor.decompress(&bytes),
+        Err(value_log::Error::Decompress(_)),
+    ));
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn compression_builtin_zstd() -> value_log::Result<()> {
+    roundtrip(value_log::ZstdCompressor::new(3))?;
+    roundtrip(value_log::ZstdCompressor::new(19))
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn compression_builtin_zstd_decompress_error() {
+    use std::error::Error;
+
+    let err = value_log::ZstdCompressor::default()
+        .decompress(b"not zstd")
+        .expect_err("should fail");
+
+    assert!(matches!(err, value_log::Error::Decompress(_)));
+    assert!(err.source().is_some());
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn compression_builtin_zstd_level_change() -> value_log::Result<()> {
+    use value_log::ZstdCompressor;
+
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    for (idx, level) in [1, 5, 9].into_iter().enumerate() {
+        let value_log = ValueLog::open(
+            vl_path,
+            Config::default().compression(ZstdCompressor::new(level)),
+        )?;
+
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        let key = idx.to_string();
+        let value = key.repeat(1_000);
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+        writer.write(&key, &value)?;
+
+        value_log.register_writer(writer)?;
+    }
+
+    let value_log = ValueLog::open(vl_path, Config::<ZstdCompressor>::default())?;
+
+    for idx in 0..3 {
+        let key = idx.to_string();
+        let vhandle = index.get(key.as_bytes())?.unwrap();
+        let value = value_log.get(&vhandle)?.unwrap();
+        assert_eq!(&*value, key.repeat(1_000).as_bytes());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use test_log::test;
+use value_log::{
+    ChecksumVerification, Compressor, Config, DescriptorTable, IndexReader, IndexWriter, MockIndex,
+    MockIndexWriter, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[test]
+fn descriptor_table_shared() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let descriptor_table = Arc::new(DescriptorTable::new(10));
+
+    let index = MockIndex::default();
+
+    let value_logs = ["a", "b"]
+        .into_iter()
+        .map(|name| {
+            ValueLog::open(
+                folder.path().join(name),
+                Config::<NoCompressor>::default()
+                    .descriptor_table(descriptor_table.clone())
+                    // NOTE: Bypass blob cache, so every read hits the file
+                    .checksum_verification(ChecksumVerification::Always),
+            )
+        })
+        .collect::<value_log::Result<Vec<_>>>()?;
+
+    for value_log in &value_logs {
+        let mut writer = value_log.get_writer()?;
+        let vhandle = writer.get_next_value_handle();
+        writer.write("a", "a")?;
+        value_log.register_writer(writer)?;
+
+        assert_eq!(&*value_log.get(&vhandle)?.unwrap(), b"a");
+    }
+
+    // NOTE: Both value logs have segment #0, but they must not share the file
+    assert_eq!(2, descriptor_table.len());
+
+    let value_log = value_logs.first().unwrap();
+
+    {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in ["a", "b", "c"] {
+            let value = key.repeat(10_000);
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key, &value)?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    let handles = ["a", "b", "c"]
+        .into_iter()
+        .map(|key| index.get(key.as_bytes()).map(Option::unwrap))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    // NOTE: Concurrent readers share the same file descriptor
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..100 {
+                    for (key, vhandle) in ["a", "b", "c"].iter().zip(&handles) {
+                        let value = value_log.get_with_prefetch(vhandle, 0).unwrap().unwrap();
+
+                        assert_eq!(&*value, key.repeat(10_000).as_bytes());
+                    }
+                }
+            });
+        }
+    });
+
+    assert_eq!(3, descriptor_table.len());
+
+    // NOTE: Dropping a segment closes its file
+    value_log.major_compact(&index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+    assert_eq!(1, descriptor_table.len());
+
+    // NOTE: Dropping a value log closes its files
+    drop(value_logs);
+    assert!(descriptor_table.is_empty());
+
+    Ok(())
+}
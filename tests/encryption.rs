@@ -0,0 +1,328 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use test_log::test;
+use value_log::{
+    Compressor, Config, Encryptor, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[derive(Clone, Default)]
+struct Lz4Compressor;
+
+impl Compressor for Lz4Compressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(lz4_flex::compress_prepend_size(bytes))
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        lz4_flex::decompress_size_prepended(bytes)
+            .map_err(|e| value_log::Error::Decompress(e.into()))
+    }
+}
+
+/// "Encrypts" by XORing every byte with the key ID
+struct XorEncryptor {
+    key_id: AtomicU32,
+}
+
+impl XorEncryptor {
+    fn new(key_id: u32) -> Arc<Self> {
+        Arc::new(Self {
+            key_id: AtomicU32::new(key_id),
+        })
+    }
+}
+
+impl Encryptor for XorEncryptor {
+    fn key_id(&self) -> u32 {
+        self.key_id.load(Ordering::Relaxed)
+    }
+
+    fn encrypt(
+        &self,
+        key_id: u32,
+        bytes: &[u8],
+        _associated_data: &[u8],
+    ) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.iter().map(|x| x ^ key_id as u8).collect())
+    }
+
+    fn decrypt(
+        &self,
+        key_id: u32,
+        bytes: &[u8],
+        associated_data: &[u8],
+    ) -> value_log::Result<Vec<u8>> {
+        self.encrypt(key_id, bytes, associated_data)
+    }
+}
+
+fn write_items<C: Compressor + Clone>(
+    value_log: &ValueLog<C>,
+    index: &MockIndex,
+    items: &[&str],
+) -> value_log::Result<()> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    for key in items {
+        let value = format!("secret-{key}").repeat(100);
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+        writer.write(key, &value)?;
+    }
+
+    value_log.register_writer(writer)
+}
+
+fn check_items<C: Compressor + Clone>(
+    value_log: &ValueLog<C>,
+    index: &MockIndex,
+    items: &[&str],
+) -> value_log::Result<()> {
+    for key in items {
+        let vhandle = index.get(key.as_bytes())?.unwrap();
+        let value = value_log.get(&vhandle)?.unwrap();
+        assert_eq!(&*value, format!("secret-{key}").repeat(100).as_bytes());
+    }
+
+    Ok(())
+}
+
+fn key_ids<C: Compressor + Clone>(value_log: &ValueLog<C>) -> Vec<Option<u32>> {
+    let mut segments = value_log.manifest.list_segments();
+    segments.sort_by_key(|x| x.id);
+    segments.iter().map(|x| x.meta.encryption_key_id).collect()
+}
+
+fn segment_files_contain(value_log_path: &std::path::Path, needle: &[u8]) -> std::io::Result<bool> {
+    for dirent in std::fs::read_dir(value_log_path.join("segments"))? {
+        let bytes = std::fs::read(dirent?.path())?;
+
+        if bytes.windows(needle.len()).any(|x| x == needle) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+#[test]
+fn encryption_key_rotation() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+    let encryptor = XorEncryptor::new(1);
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<NoCompressor>::default().encryption(encryptor.clone()),
+    )?;
+
+    write_items(&value_log, &index, &["a", "b"])?;
+    assert!(!segment_files_contain(vl_path, b"secret-a")?);
+
+    encryptor.key_id.store(2, Ordering::Relaxed);
+    write_items(&value_log, &index, &["c", "d"])?;
+
+    assert_eq!(vec![Some(1), Some(2)], key_ids(&value_log));
+    check_items(&value_log, &index, &["a", "b", "c", "d"])?;
+    assert_eq!(0, value_log.verify()?);
+
+    // NOTE: Rolling over the old segment re-encrypts it with the new key
+    let old_segment_ids = value_log
+        .manifest
+        .list_segments()
+        .iter()
+        .filter(|x| x.meta.encryption_key_id == Some(1))
+        .map(|x| x.id)
+        .collect::<Vec<_>>();
+
+    value_log.rollover(&old_segment_ids, &index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+
+    assert_eq!(vec![Some(2), Some(2)], key_ids(&value_log));
+    check_items(&value_log, &index, &["a", "b", "c", "d"])?;
+
+    Ok(())
+}
+
+#[test]
+fn encryption_after_compression() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    {
+        let value_log = ValueLog::open(
+            vl_path,
+            Config::<Lz4Compressor>::default().encryption(XorEncryptor::new(7)),
+        )?;
+
+        let mut writer = value_log.get_writer()?;
+        let value = "secret-a".repeat(100);
+
+        let vhandle = writer.get_next_value_handle();
+        MockIndexWriter(index.clone()).insert_indirect(b"a", vhandle, value.len() as u32)?;
+
+        let written_bytes = writer.write("a", &value)?;
+        assert!(written_bytes < value.len() as u32);
+
+        value_log.register_writer(writer)?;
+
+        check_items(&value_log, &index, &["a"])?;
+    }
+
+    {
+        let value_log = ValueLog::open(
+            vl_path,
+            Config::<Lz4Compressor>::default().encryption(XorEncryptor::new(7)),
+        )?;
+        check_items(&value_log, &index, &["a"])?;
+    }
+
+    assert!(matches!(
+        ValueLog::open(vl_path, Config::<Lz4Compressor>::default()),
+        Err(value_log::Error::MissingEncryptor),
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "aes-gcm")]
+fn encryption_aes_gcm() -> value_log::Result<()> {
+    use value_log::AesGcmEncryptor;
+
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    {
+        let value_log = ValueLog::open(
+            vl_path,
+            Config::<NoCompressor>::default()
+                .encryption(Arc::new(AesGcmEncryptor::new(1, &[1; 32]))),
+        )?;
+
+        write_items(&value_log, &index, &["a", "b"])?;
+        assert!(!segment_files_contain(vl_path, b"secret-a")?);
+        check_items(&value_log, &index, &["a", "b"])?;
+    }
+
+    {
+        let value_log = ValueLog::open(
+            vl_path,
+            Config::<NoCompressor>::default().encryption(Arc::new(
+                AesGcmEncryptor::new(2, &[2; 32]).with_retired_key(1, &[1; 32]),
+            )),
+        )?;
+
+        write_items(&value_log, &index, &["c"])?;
+        assert_eq!(vec![Some(1), Some(2)], key_ids(&value_log));
+        check_items(&value_log, &index, &["a", "b", "c"])?;
+    }
+
+    {
+        // NOTE: Key 1 is wrong
+        let value_log = ValueLog::open(
+            vl_path,
+            Config::<NoCompressor>::default().encryption(Arc::new(
+                AesGcmEncryptor::new(2, &[2; 32]).with_retired_key(1, &[3; 32]),
+            )),
+        )?;
+
+        check_items(&value_log, &index, &["c"])?;
+
+        let vhandle = index.get(b"a")?.unwrap();
+        assert!(matches!(
+            value_log.get(&vhandle),
+            Err(value_log::Error::Decrypt(_)),
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "aes-gcm")]
+fn encryption_aes_gcm_swapped_chunks() -> value_log::Result<()> {
+    use std::io::Read;
+    use value_log::{AesGcmEncryptor, ChecksumVerification};
+
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    // NOTE: Checksums are not verified, so only the encryption can detect the swap
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<NoCompressor>::default()
+            .encryption(Arc::new(AesGcmEncryptor::new(1, &[1; 32])))
+            .large_blob_threshold(100_000)
+            .checksum_verification(ChecksumVerification::Never),
+    )?;
+
+    let value = (0..200_000u32)
+        .map(|x| (x / 65_536) as u8)
+        .collect::<Vec<_>>();
+
+    {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(b"large", vhandle, value.len() as u32)?;
+
+        writer.write(b"large", &value)?;
+        value_log.register_writer(writer)?;
+    }
+
+    let vhandle = index.get(b"large")?.unwrap();
+    let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+
+    // NOTE: Swap the first two chunks, which have the same length
+    {
+        // magic + flags + checksum + key len + key + value len + chunk table (4 chunks)
+        let data_start =
+            vhandle.offset as usize + 8 + 1 + 8 + 2 + b"large".len() + 4 + 12 + 4 * 13 + 8;
+
+        // nonce + chunk + tag
+        let chunk_len = 12 + 65_536 + 16;
+
+        let mut bytes = std::fs::read(&segment.path)?;
+        let (first, rest) = bytes[data_start..].split_at_mut(chunk_len);
+        first.swap_with_slice(&mut rest[..chunk_len]);
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    assert!(matches!(
+        value_log.get(&vhandle),
+        Err(value_log::Error::Decrypt(_)),
+    ));
+
+    let mut reader = value_log.open_blob(&vhandle)?.unwrap();
+    assert!(reader.read_to_end(&mut vec![]).is_err());
+
+    Ok(())
+}
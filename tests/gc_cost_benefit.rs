@@ -0,0 +1,124 @@
+use std::time::Duration;
+use test_log::test;
+use value_log::{
+    Compressor, Config, CostBenefitStrategy, GcStrategy, IndexWriter, MockIndex, MockIndexWriter,
+    ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn write_segment(
+    value_log: &ValueLog<NoCompressor>,
+    index: &MockIndex,
+    keys: &[&str],
+) -> value_log::Result<u64> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+    let segment_id = writer.get_next_value_handle().segment_id;
+
+    for key in keys {
+        let value = key.repeat(1_000);
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+        writer.write(key.as_bytes(), value.as_bytes())?;
+    }
+
+    value_log.register_writer(writer)?;
+
+    Ok(segment_id)
+}
+
+#[test]
+fn gc_cost_benefit_prefers_old_segments() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let old = write_segment(&value_log, &index, &["a", "b"])?;
+    std::thread::sleep(Duration::from_millis(50));
+    let young = write_segment(&value_log, &index, &["c", "d"])?;
+    std::thread::sleep(Duration::from_millis(50));
+
+    // NOTE: Both segments are 50% stale
+    write_segment(&value_log, &index, &["a", "c"])?;
+    value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+
+    let old_segment = value_log.manifest.get_segment(old).unwrap();
+    let young_segment = value_log.manifest.get_segment(young).unwrap();
+    assert_eq!(0.5, old_segment.stale_ratio());
+    assert_eq!(0.5, young_segment.stale_ratio());
+    assert!(old_segment.created_at() < young_segment.created_at());
+
+    let strategy = CostBenefitStrategy::new(1);
+    assert_eq!(vec![old], strategy.pick(&value_log));
+
+    let strategy = CostBenefitStrategy::new(10);
+    assert_eq!(vec![old, young], strategy.pick(&value_log));
+
+    // NOTE: The rewritten data keeps the age of the youngest segment it came from
+    let created_at = young_segment.created_at();
+    value_log.apply_gc_strategy(&strategy, &index, MockIndexWriter(index.clone()))?;
+
+    // NOTE: Stale segments are not picked again before they are dropped
+    assert!(strategy.pick(&value_log).is_empty());
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(2, value_log.segment_count());
+
+    let rewritten = value_log
+        .manifest
+        .list_segments()
+        .into_iter()
+        .map(|x| x.created_at())
+        .min()
+        .unwrap();
+    assert_eq!(created_at, rewritten);
+
+    Ok(())
+}
+
+#[test]
+fn gc_cost_benefit_created_at_recovery() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let created_at = {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        let id = write_segment(&value_log, &index, &["a"])?;
+        value_log.manifest.get_segment(id).unwrap().created_at()
+    };
+    assert!(created_at > 0);
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        let segments = value_log.manifest.list_segments();
+        assert_eq!(created_at, segments.first().unwrap().created_at());
+    }
+
+    // NOTE: V1 segments fall back to the file's modification time
+    let value_log = ValueLog::open("test_fixture/v1_vlog", Config::<NoCompressor>::default())?;
+
+    for segment in value_log.manifest.list_segments() {
+        assert!(segment.created_at() > 0);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use test_log::test;
+use value_log::{
+    Compressor, Config, ExpiredRatioStrategy, GcStrategy, IndexWriter, MockIndex, MockIndexWriter,
+    ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[test]
+fn gc_expired_ratio() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value = "value".repeat(1_000);
+    let expires_later = now() + 3_600_000;
+
+    let (mostly_expired, rarely_expired) = {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        let mut ids = vec![];
+
+        for expired_count in [3, 1] {
+            let mut index_writer = MockIndexWriter(index.clone());
+            let mut writer = value_log.get_writer()?;
+            ids.push(writer.get_next_value_handle().segment_id);
+
+            for idx in 0..4 {
+                let key = format!("{}-{idx}", ids.len());
+                let expires_at = if idx < expired_count {
+                    now() - 1
+                } else {
+                    expires_later
+                };
+
+                let vhandle = writer.get_next_value_handle();
+                index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+                writer.write_with_expiry(key, &value, expires_at)?;
+            }
+
+            value_log.register_writer(writer)?;
+        }
+
+        (ids[0], ids[1])
+    };
+
+    // NOTE: The expiry histogram is recovered from the segment metadata,
+    // so no index scan is needed
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let segment = value_log.manifest.get_segment(mostly_expired).unwrap();
+    assert_eq!(0.75, segment.expired_ratio(now()));
+    assert_eq!(0.0, segment.expired_ratio(0));
+    assert_eq!(1.0, segment.expired_ratio(expires_later));
+
+    let segment = value_log.manifest.get_segment(rarely_expired).unwrap();
+    assert_eq!(0.25, segment.expired_ratio(now()));
+
+    let strategy = ExpiredRatioStrategy::new(0.5);
+    assert_eq!(vec![mostly_expired], strategy.pick(&value_log));
+
+    value_log.apply_gc_strategy(&strategy, &index, MockIndexWriter(index.clone()))?;
+
+    // NOTE: Stale segments are not picked again before they are dropped
+    assert!(strategy.pick(&value_log).is_empty());
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(2, value_log.segment_count());
+    assert!(value_log.manifest.get_segment(mostly_expired).is_none());
+
+    // NOTE: Only the blob that has not expired was rewritten
+    let rewritten = value_log
+        .manifest
+        .list_segments()
+        .into_iter()
+        .find(|x| x.id != rarely_expired)
+        .unwrap();
+    assert_eq!(1, rewritten.meta.item_count);
+    assert_eq!(0.0, rewritten.expired_ratio(now()));
+
+    let (vhandle, _) = index
+        .read()
+        .unwrap()
+        .get(b"1-3".as_slice())
+        .cloned()
+        .unwrap();
+    assert_eq!(Some(value.as_bytes().into()), value_log.get(&vhandle)?);
+
+    Ok(())
+}
+
+#[test]
+fn gc_expired_ratio_fully_expired() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let value = "value".repeat(1_000);
+
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+    let segment_id = writer.get_next_value_handle().segment_id;
+
+    for key in ["a", "b"] {
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+        writer.write_with_expiry(key, &value, now() - 1)?;
+    }
+
+    value_log.register_writer(writer)?;
+
+    // NOTE: The threshold is inclusive, so fully expired segments are picked
+    let strategy = ExpiredRatioStrategy::new(1.0);
+    assert_eq!(vec![segment_id], strategy.pick(&value_log));
+
+    value_log.apply_gc_strategy(&strategy, &index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+    assert_eq!(0, value_log.segment_count());
+
+    Ok(())
+}
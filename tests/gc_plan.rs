@@ -0,0 +1,99 @@
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexWriter, MockIndex, MockIndexWriter, SpaceAmpStrategy,
+    StaleThresholdStrategy, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[test]
+fn gc_plan() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let value = "value".repeat(1_000);
+
+    // NOTE: Write 4 segments with 2 items each, then overwrite one
+    // item of each segment -> 4 half-stale segments + 1 live segment
+    for keys in [["a", "b"], ["c", "d"], ["e", "f"], ["g", "h"], ["a", "c"]] {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in keys {
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key.as_bytes(), value.as_bytes())?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in ["e", "g"] {
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key.as_bytes(), value.as_bytes())?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+    assert_eq!(1.5, value_log.space_amp());
+
+    let disk_space_before = value_log.manifest.disk_space_used();
+    let segment_count_before = value_log.segment_count();
+
+    let plan = value_log.plan_gc(&StaleThresholdStrategy::new(0.4));
+    assert_eq!(4, plan.segment_ids.len());
+    assert_eq!(4 * value.len() as u64, plan.live_bytes_to_rewrite);
+    assert_eq!(4 * value.len() as u64, plan.stale_bytes_to_free);
+    assert_eq!(8 * value.len() as u64, plan.bytes_to_read);
+    assert_eq!(1.5, plan.space_amp_before);
+    assert_eq!(1.0, plan.space_amp_after);
+
+    let plan = value_log.plan_gc(&StaleThresholdStrategy::new(0.9));
+    assert!(plan.is_empty());
+    assert_eq!(plan.space_amp_before, plan.space_amp_after);
+
+    let plan = value_log.plan_gc(&SpaceAmpStrategy::new(1.3));
+    assert!(!plan.is_empty());
+    assert!(plan.space_amp_after <= 1.3);
+
+    // NOTE: Planning does not change anything
+    assert_eq!(disk_space_before, value_log.manifest.disk_space_used());
+    assert_eq!(segment_count_before, value_log.segment_count());
+    assert_eq!(1.5, value_log.space_amp());
+
+    // NOTE: The projection matches the actual result
+    let plan = value_log.plan_gc(&StaleThresholdStrategy::new(0.4));
+    value_log.apply_gc_strategy(
+        &StaleThresholdStrategy::new(0.4),
+        &index,
+        MockIndexWriter(index.clone()),
+    )?;
+    value_log.drop_stale_segments()?;
+    assert_eq!(plan.space_amp_after, value_log.space_amp());
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexWriter, MockIndex, MockIndexWriter, SpaceAmpStrategy, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[test]
+fn gc_stats_recovery() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let key = b"key";
+    let value = "value".repeat(5_000);
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        // NOTE: Write a single item 4x
+        // -> should result in space amp = 4.0x
+        for _ in 0..4 {
+            let mut index_writer = MockIndexWriter(index.clone());
+            let mut writer = value_log.get_writer()?;
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key, vhandle, value.len() as u32)?;
+
+            writer.write(key, value.as_bytes())?;
+            value_log.register_writer(writer)?;
+        }
+
+        value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+        assert_eq!(4.0, value_log.space_amp());
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        assert_eq!(4.0, value_log.space_amp());
+        assert_eq!(3 * value.len() as u64, value_log.manifest.stale_bytes());
+
+        // NOTE: The GC strategy can act on the recovered stats without rescanning
+        let strategy = SpaceAmpStrategy::new(1.0);
+        value_log.apply_gc_strategy(&strategy, &index, MockIndexWriter(index.clone()))?;
+
+        // NOTE: The rolled over segments are marked as stale
+        assert_eq!(4, value_log.segment_count());
+        assert_eq!(
+            3,
+            value_log
+                .manifest
+                .list_segments()
+                .iter()
+                .filter(|x| x.is_stale())
+                .count()
+        );
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        assert_eq!(4, value_log.segment_count());
+
+        // NOTE: Staleness survived the restart, so the segments can be dropped
+        value_log.drop_stale_segments()?;
+        assert_eq!(1, value_log.segment_count());
+        assert_eq!(1.0, value_log.space_amp());
+    }
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+    assert_eq!(1, value_log.segment_count());
+    assert_eq!(1.0, value_log.space_amp());
+
+    Ok(())
+}
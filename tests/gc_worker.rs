@@ -0,0 +1,371 @@
+use std::{
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+use test_log::test;
+use value_log::{
+    Compressor, Config, GcStrategy, GcWorkerConfig, IndexWriter, MockIndex, MockIndexWriter,
+    SpaceAmpStrategy, StaleThresholdStrategy, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn write_garbage(value_log: &ValueLog<NoCompressor>, index: &MockIndex) -> value_log::Result<()> {
+    let key = b"key";
+    let value = "value".repeat(1_000);
+
+    // NOTE: Write a single item 4x
+    // -> should result in space amp = 4.0x
+    for _ in 0..4 {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key, vhandle, value.len() as u32)?;
+
+        writer.write(key, value.as_bytes())?;
+        value_log.register_writer(writer)?;
+    }
+
+    Ok(())
+}
+
+fn write_partially_stale(
+    value_log: &ValueLog<NoCompressor>,
+    index: &MockIndex,
+) -> value_log::Result<()> {
+    let value = "value".repeat(1_000);
+
+    // NOTE: Every segment has a live blob, and a blob that is overwritten by the next segment
+    // -> the first 3 segments are half stale
+    for idx in 0..4 {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in [format!("live{idx}"), "dead".into()] {
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key, value.as_bytes())?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Upper bound for waiting on the worker, so a broken worker fails the test instead of hanging it
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Events reported by the worker
+struct Events {
+    /// Result of every finished run
+    runs: Receiver<bool>,
+
+    /// Sent whenever the worker starts rewriting a batch of segments
+    batches: Receiver<()>,
+}
+
+impl Events {
+    /// Waits for finished runs until the condition holds.
+    fn wait_for_runs(&self, mut f: impl FnMut() -> bool) -> bool {
+        while !f() {
+            match self.runs.recv_timeout(TIMEOUT) {
+                Ok(is_ok) => assert!(is_ok),
+                Err(_) => return false,
+            }
+        }
+
+        true
+    }
+}
+
+fn start_worker<S: GcStrategy<NoCompressor> + Send + 'static>(
+    value_log: &ValueLog<NoCompressor>,
+    index: &MockIndex,
+    config: GcWorkerConfig,
+    strategy: S,
+) -> value_log::Result<(value_log::GcWorker, Events)> {
+    let scan_index = index.clone();
+    let reader_index = index.clone();
+    let writer_index = index.clone();
+
+    let (run_tx, runs) = mpsc::channel();
+    let (batch_tx, batches) = mpsc::channel();
+
+    let worker = value_log.start_gc_worker(
+        config
+            .poll_interval(Duration::from_millis(5))
+            .scan_for_stats(move || {
+                scan_index
+                    .read()
+                    .unwrap()
+                    .values()
+                    .cloned()
+                    .map(Ok)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+            })
+            .on_run(move |result| {
+                let _ = run_tx.send(result.is_ok());
+            }),
+        strategy,
+        move || reader_index.clone(),
+        move || {
+            let _ = batch_tx.send(());
+            MockIndexWriter(writer_index.clone())
+        },
+    )?;
+
+    Ok((worker, Events { runs, batches }))
+}
+
+#[test]
+fn gc_worker_space_amp_trigger() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    let (worker, events) = start_worker(
+        &value_log,
+        &index,
+        GcWorkerConfig::default()
+            .interval(None)
+            .space_amp_trigger(2.0)
+            .drop_stale_segments(true),
+        SpaceAmpStrategy::new(1.0),
+    )?;
+    assert!(worker.is_running());
+
+    // NOTE: Stats are not known until the first scan, so wake the worker once
+    write_garbage(&value_log, &index)?;
+    worker.wake();
+    assert!(events.wait_for_runs(|| value_log.segment_count() == 1));
+
+    // NOTE: From now on, the worker is triggered by the tracked space amp
+    write_garbage(&value_log, &index)?;
+    value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+    assert!(events.wait_for_runs(|| value_log.segment_count() == 1));
+    assert_eq!(1.0, value_log.space_amp());
+
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    worker.shutdown();
+    assert!(!worker.is_running());
+
+    Ok(())
+}
+
+#[test]
+fn gc_worker_space_amp_backoff() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    write_garbage(&value_log, &index)?;
+    value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+    assert!(value_log.space_amp() >= 2.0);
+
+    // NOTE: The strategy never picks anything
+    let (worker, events) = start_worker(
+        &value_log,
+        &index,
+        GcWorkerConfig::default()
+            .interval(None)
+            .space_amp_trigger(2.0),
+        StaleThresholdStrategy::new(1.0),
+    )?;
+
+    assert!(events.runs.recv_timeout(TIMEOUT).unwrap());
+
+    // NOTE: The stats did not change, so the worker does not run again on every poll
+    assert!(events
+        .runs
+        .recv_timeout(Duration::from_millis(100))
+        .is_err());
+
+    // NOTE: New stats trigger the worker again
+    write_garbage(&value_log, &index)?;
+    value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+    assert!(events.runs.recv_timeout(TIMEOUT).unwrap());
+
+    worker.shutdown();
+    assert_eq!(8, value_log.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn gc_worker_pause_resume() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    let (worker, events) = start_worker(
+        &value_log,
+        &index,
+        GcWorkerConfig::default()
+            .interval(None)
+            .drop_stale_segments(true),
+        SpaceAmpStrategy::new(1.0),
+    )?;
+
+    worker.pause();
+    assert!(worker.is_paused());
+
+    // NOTE: The wake-up is deferred until the worker is resumed
+    write_garbage(&value_log, &index)?;
+    worker.wake();
+    assert!(events
+        .runs
+        .recv_timeout(Duration::from_millis(100))
+        .is_err());
+    assert_eq!(4, value_log.segment_count());
+
+    worker.resume();
+    assert!(events.wait_for_runs(|| value_log.segment_count() == 1));
+
+    Ok(())
+}
+
+#[test]
+fn gc_worker_batches_segments() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    write_partially_stale(&value_log, &index)?;
+
+    let (worker, events) = start_worker(
+        &value_log,
+        &index,
+        GcWorkerConfig::default()
+            .interval(None)
+            .drop_stale_segments(true),
+        SpaceAmpStrategy::new(1.0),
+    )?;
+    worker.wake();
+
+    // NOTE: The half stale segments are rewritten into a single segment
+    assert!(events.wait_for_runs(|| value_log.segment_count() == 2));
+    assert_eq!(1, events.batches.try_iter().count());
+    worker.shutdown();
+
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gc_worker_keeps_stale_segments() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    write_partially_stale(&value_log, &index)?;
+
+    let (worker, events) = start_worker(
+        &value_log,
+        &index,
+        GcWorkerConfig::default().interval(None),
+        SpaceAmpStrategy::new(1.0),
+    )?;
+    worker.wake();
+
+    // NOTE: The rewritten segments are only marked as stale,
+    // the index decides when they can be dropped
+    assert!(events.runs.recv_timeout(TIMEOUT).unwrap());
+    worker.shutdown();
+    assert_eq!(5, value_log.segment_count());
+
+    assert!(value_log.drop_stale_segments()? > 0);
+    assert_eq!(2, value_log.segment_count());
+
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gc_worker_rate_limit() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    write_partially_stale(&value_log, &index)?;
+
+    // NOTE: Only one segment fits into a batch, and rewriting its live blob
+    // costs thousands of seconds worth of budget, so the worker is throttled
+    // for much longer than the test runs
+    let (worker, events) = start_worker(
+        &value_log,
+        &index,
+        GcWorkerConfig::default()
+            .interval(None)
+            .max_bytes_per_second(1)
+            .drop_stale_segments(true),
+        SpaceAmpStrategy::new(1.0),
+    )?;
+    worker.wake();
+
+    events.batches.recv_timeout(TIMEOUT).unwrap();
+
+    // NOTE: Shutting down interrupts the throttled worker before it gets to drop segments
+    worker.shutdown();
+    assert!(events.runs.recv_timeout(TIMEOUT).unwrap());
+
+    // NOTE: No other batch was started
+    assert_eq!(0, events.batches.try_iter().count());
+    assert_eq!(5, value_log.segment_count());
+
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gc_worker_drop_value_log() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    let (worker, _) = start_worker(
+        &value_log,
+        &index,
+        GcWorkerConfig::default(),
+        SpaceAmpStrategy::new(1.0),
+    )?;
+    assert!(value_log.gc_worker().is_some());
+
+    drop(value_log);
+    assert!(!worker.is_running());
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[test]
+fn get_checked_key_mismatch() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in ["a", "b"] {
+            let value = key.repeat(1_000);
+            let value = value.as_bytes();
+
+            let key = key.as_bytes();
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key, vhandle, value.len() as u32)?;
+
+            writer.write(key, value)?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    let handle_a = index.get(b"a")?.unwrap();
+    let handle_b = index.get(b"b")?.unwrap();
+
+    // NOTE: Run twice, second time is served from blob cache
+    for _ in 0..2 {
+        assert_eq!(
+            &*value_log.get_checked(b"a", &handle_a)?.unwrap(),
+            "a".repeat(1_000).as_bytes(),
+        );
+
+        match value_log.get_checked(b"a", &handle_b) {
+            Err(value_log::Error::KeyMismatch { segment_id, offset }) => {
+                assert_eq!(handle_b.segment_id, segment_id);
+                assert_eq!(handle_b.offset, offset);
+            }
+            other => panic!("expected key mismatch, got {other:?}"),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use rand::RngCore;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use test_log::test;
+use value_log::{
+    ChecksumVerification, Compressor, Config, IndexWriter, MockIndex, MockIndexWriter, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct CountingLz4Compressor {
+    decompressions: Arc<AtomicUsize>,
+}
+
+impl Compressor for CountingLz4Compressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(lz4_flex::compress_prepend_size(bytes))
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        self.decompressions.fetch_add(1, Ordering::Relaxed);
+        lz4_flex::decompress_size_prepended(bytes)
+            .map_err(|e| value_log::Error::Decompress(e.into()))
+    }
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+#[test]
+fn incompressible_blob_stored_raw() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let compressor = CountingLz4Compressor::default();
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::default().compression(compressor.clone()))?;
+
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    let raw_value = random_bytes(4_096);
+    let compressible_value = b"verycompressable".repeat(256);
+
+    let raw_handle = writer.get_next_value_handle();
+    index_writer.insert_indirect(b"raw", raw_handle.clone(), raw_value.len() as u32)?;
+    let written_bytes = writer.write("raw", &raw_value)?;
+    assert_eq!(written_bytes, raw_value.len() as u32);
+
+    let compressed_handle = writer.get_next_value_handle();
+    index_writer.insert_indirect(
+        b"compressed",
+        compressed_handle.clone(),
+        compressible_value.len() as u32,
+    )?;
+    let written_bytes = writer.write("compressed", &compressible_value)?;
+    assert!(written_bytes < compressible_value.len() as u32);
+
+    value_log.register_writer(writer)?;
+
+    assert_eq!(
+        &*value_log.get(&raw_handle)?.expect("should exist"),
+        &*raw_value,
+    );
+    assert_eq!(0, compressor.decompressions.load(Ordering::Relaxed));
+
+    assert_eq!(
+        &*value_log.get(&compressed_handle)?.expect("should exist"),
+        &*compressible_value,
+    );
+    assert_eq!(1, compressor.decompressions.load(Ordering::Relaxed));
+
+    assert_eq!(0, value_log.verify()?);
+
+    // NOTE: Rollover needs to keep the raw blob raw
+    value_log.major_compact(&index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+
+    {
+        let segments = value_log.manifest.list_segments();
+        let segment = segments.first().unwrap();
+        assert!(segment.meta.compressed_bytes > raw_value.len() as u64);
+        assert!(
+            segment.meta.compressed_bytes
+                < raw_value.len() as u64 + compressible_value.len() as u64
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn incompressible_blob_min_compression_savings() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    // NOTE: Compresses to roughly half its size
+    let mut value = random_bytes(2_048);
+    value.extend(std::iter::repeat(0).take(2_048));
+
+    {
+        let value_log = ValueLog::open(
+            vl_path.join("default"),
+            Config::<CountingLz4Compressor>::default(),
+        )?;
+
+        let mut writer = value_log.get_writer()?;
+        let vhandle = writer.get_next_value_handle();
+        let written_bytes = writer.write("a", &value)?;
+        assert!(written_bytes < value.len() as u32);
+        value_log.register_writer(writer)?;
+
+        assert_eq!(&*value_log.get(&vhandle)?.expect("should exist"), &*value);
+    }
+
+    {
+        let value_log = ValueLog::open(
+            vl_path.join("strict"),
+            Config::<CountingLz4Compressor>::default().min_compression_savings(0.75),
+        )?;
+
+        let mut writer = value_log.get_writer()?;
+        let vhandle = writer.get_next_value_handle();
+        let written_bytes = writer.write("a", &value)?;
+        assert_eq!(written_bytes, value.len() as u32);
+        value_log.register_writer(writer)?;
+
+        assert_eq!(&*value_log.get(&vhandle)?.expect("should exist"), &*value);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn incompressible_blob_flag_corruption() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let value = b"verycompressable".repeat(256);
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<CountingLz4Compressor>::default()
+            .checksum_verification(ChecksumVerification::Always),
+    )?;
+
+    let mut writer = value_log.get_writer()?;
+    let vhandle = writer.get_next_value_handle();
+    let written_bytes = writer.write("a", &value)?;
+    assert!(written_bytes < value.len() as u32);
+    value_log.register_writer(writer)?;
+
+    // NOTE: Clear the compressed flag, which follows the header magic
+    {
+        let segment = value_log.manifest.get_segment(vhandle.segment_id).unwrap();
+
+        let mut bytes = std::fs::read(&segment.path)?;
+        bytes[vhandle.offset as usize + 8] &= !0b0000_0001;
+        std::fs::write(&segment.path, bytes)?;
+    }
+
+    // NOTE: The compressed bytes must not be returned as the value
+    assert!(matches!(
+        value_log.get(&vhandle),
+        Err(value_log::Error::ChecksumMismatch { .. }),
+    ));
+
+    assert_eq!(1, value_log.verify()?);
+
+    Ok(())
+}
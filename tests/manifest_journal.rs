@@ -0,0 +1,247 @@
+use std::io::Write;
+use test_log::test;
+use value_log::{Compressor, Config, ValueLog};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn write_segment(value_log: &ValueLog<NoCompressor>) -> value_log::Result<()> {
+    let mut writer = value_log.get_writer()?;
+    writer.write("a", "a")?;
+    value_log.register_writer(writer)
+}
+
+fn sorted_ids(value_log: &ValueLog<NoCompressor>) -> Vec<u64> {
+    let mut ids = value_log.manifest.list_segment_ids();
+    ids.sort_unstable();
+    ids
+}
+
+#[test]
+fn manifest_journal_recover() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let ids = {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        for _ in 0..10 {
+            write_segment(&value_log)?;
+        }
+
+        let ids = sorted_ids(&value_log);
+        value_log.manifest.drop_segments(&ids[..5])?;
+
+        sorted_ids(&value_log)
+    };
+    assert_eq!(5, ids.len());
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+    assert_eq!(ids, sorted_ids(&value_log));
+
+    Ok(())
+}
+
+#[test]
+fn manifest_journal_compaction() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+    let manifest_path = vl_path.join("vlog_manifest");
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let mut max_size = 0;
+
+    for _ in 0..1_000 {
+        write_segment(&value_log)?;
+
+        let ids = value_log.manifest.list_segment_ids();
+        value_log.manifest.drop_segments(&ids)?;
+
+        max_size = max_size.max(std::fs::metadata(&manifest_path)?.len());
+    }
+
+    // NOTE: 2000 edits would be ~2000 * 29 bytes without compaction
+    assert!(max_size < 10_000, "manifest is not compacted: {max_size}B");
+
+    write_segment(&value_log)?;
+    let ids = sorted_ids(&value_log);
+    drop(value_log);
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+    assert_eq!(ids, sorted_ids(&value_log));
+
+    Ok(())
+}
+
+#[test]
+fn manifest_journal_torn_tail() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+    let manifest_path = vl_path.join("vlog_manifest");
+
+    let ids = {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        write_segment(&value_log)?;
+        write_segment(&value_log)?;
+        sorted_ids(&value_log)
+    };
+
+    // NOTE: Simulate a crash while appending a record
+    {
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&manifest_path)?;
+        file.write_all(&[0, 0, 0, 9, 1, 2, 3])?;
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        assert_eq!(ids, sorted_ids(&value_log));
+
+        // NOTE: Writing after a torn tail must not corrupt the manifest
+        write_segment(&value_log)?;
+        assert_eq!(3, value_log.segment_count());
+    }
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+    assert_eq!(3, value_log.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn manifest_journal_corrupt_record() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+    let manifest_path = vl_path.join("vlog_manifest");
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        write_segment(&value_log)?;
+        write_segment(&value_log)?;
+    }
+
+    // NOTE: Flip a bit in the first record's payload
+    {
+        let mut bytes = std::fs::read(&manifest_path)?;
+        let payload_start = 8 + 4 + 8 + 8;
+        *bytes.get_mut(payload_start + 3).unwrap() ^= 1;
+        std::fs::write(&manifest_path, bytes)?;
+    }
+
+    assert!(matches!(
+        ValueLog::open(vl_path, Config::<NoCompressor>::default()),
+        Err(value_log::Error::Decode(_)),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn manifest_journal_corrupt_record_length() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+    let manifest_path = vl_path.join("vlog_manifest");
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        write_segment(&value_log)?;
+        write_segment(&value_log)?;
+    }
+
+    // NOTE: Make the first record's length run past the end of the file,
+    // which must not be mistaken for a partially written record
+    {
+        let mut bytes = std::fs::read(&manifest_path)?;
+        *bytes.get_mut(8).unwrap() ^= 0x10;
+        std::fs::write(&manifest_path, bytes)?;
+    }
+
+    assert!(matches!(
+        ValueLog::open(vl_path, Config::<NoCompressor>::default()),
+        Err(value_log::Error::Decode(_)),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn manifest_journal_corrupt_header() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+    let manifest_path = vl_path.join("vlog_manifest");
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        write_segment(&value_log)?;
+    }
+
+    // NOTE: Corrupt the magic, which must not fall back to the legacy format
+    {
+        let mut bytes = std::fs::read(&manifest_path)?;
+        *bytes.get_mut(2).unwrap() ^= 1;
+        std::fs::write(&manifest_path, bytes)?;
+    }
+
+    assert!(matches!(
+        ValueLog::open(vl_path, Config::<NoCompressor>::default()),
+        Err(e @ value_log::Error::Decode(_)) if format!("{e:?}").contains("InvalidHeader"),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn manifest_journal_migrate_legacy() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    // NOTE: Copy fixture, so we can write into it
+    let fixture_path = std::path::Path::new("test_fixture/v1_vlog");
+    std::fs::create_dir_all(vl_path.join("segments"))?;
+    std::fs::copy(fixture_path.join(".vlog"), vl_path.join(".vlog"))?;
+    std::fs::copy(
+        fixture_path.join("vlog_manifest"),
+        vl_path.join("vlog_manifest"),
+    )?;
+    for dirent in std::fs::read_dir(fixture_path.join("segments"))? {
+        let dirent = dirent?;
+        std::fs::copy(
+            dirent.path(),
+            vl_path.join("segments").join(dirent.file_name()),
+        )?;
+    }
+
+    let legacy_manifest = std::fs::read(vl_path.join("vlog_manifest"))?;
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        assert_eq!(2, value_log.segment_count());
+
+        // NOTE: Opening does not touch the legacy manifest
+        assert_eq!(
+            legacy_manifest,
+            std::fs::read(vl_path.join("vlog_manifest"))?
+        );
+
+        write_segment(&value_log)?;
+        assert!(std::fs::read(vl_path.join("vlog_manifest"))?.starts_with(b"VLOGMAN"));
+    }
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+    assert_eq!(3, value_log.segment_count());
+    assert_eq!(0, value_log.verify()?);
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[test]
+fn mark_blob_stale() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let key = b"key";
+    let value = "value".repeat(5_000);
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+        let mut handles = vec![];
+
+        // NOTE: Write a single item 4x, reporting every overwritten blob
+        for _ in 0..4 {
+            let mut index_writer = MockIndexWriter(index.clone());
+            let mut writer = value_log.get_writer()?;
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key, vhandle.clone(), value.len() as u32)?;
+
+            writer.write(key, value.as_bytes())?;
+            value_log.register_writer(writer)?;
+
+            if let Some(previous) = handles.last() {
+                value_log.mark_blob_stale(previous, value.len() as u32)?;
+            }
+
+            handles.push(vhandle);
+        }
+
+        // NOTE: No scan needed
+        assert_eq!(4.0, value_log.space_amp());
+        assert_eq!(3 * value.len() as u64, value_log.manifest.stale_bytes());
+
+        // NOTE: Reporting a blob twice does not exceed the segment's totals
+        let vhandle = handles.first().expect("should exist");
+        value_log.mark_blobs_stale(&[(vhandle.clone(), value.len() as u32)])?;
+        assert_eq!(3 * value.len() as u64, value_log.manifest.stale_bytes());
+
+        // NOTE: A scan reconciles the stats with the index
+        value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+        assert_eq!(4.0, value_log.space_amp());
+        assert_eq!(3 * value.len() as u64, value_log.manifest.stale_bytes());
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+        assert_eq!(4.0, value_log.space_amp());
+
+        value_log.drop_stale_segments()?;
+        assert_eq!(1, value_log.segment_count());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn mark_blob_stale_unknown_segment() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let vhandle = value_log::ValueHandle {
+        segment_id: 1_000,
+        offset: 0,
+    };
+    value_log.mark_blob_stale(&vhandle, 100)?;
+
+    assert_eq!(0, value_log.manifest.stale_bytes());
+
+    Ok(())
+}
+
+#[test]
+fn mark_blob_stale_needs_confirmation() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value = "value".repeat(5_000);
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    for key in [b"a", b"b"] {
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key, vhandle, value.len() as u32)?;
+        writer.write(key, value.as_bytes())?;
+    }
+
+    value_log.register_writer(writer)?;
+
+    // NOTE: Report "a" multiple times, while "b" is still alive
+    let vhandle = index.get(b"a")?.expect("should exist");
+    for _ in 0..3 {
+        value_log.mark_blob_stale(&vhandle, value.len() as u32)?;
+    }
+
+    // NOTE: Reports alone must not make the segment droppable
+    assert_eq!(0, value_log.drop_stale_segments()?);
+    assert_eq!(1, value_log.segment_count());
+
+    let vhandle = index.get(b"b")?.expect("should exist");
+    assert_eq!(
+        value.as_bytes(),
+        &*value_log.get(&vhandle)?.expect("should exist")
+    );
+
+    // NOTE: Once both blobs are gone, a scan confirms the segment is stale
+    index.remove(b"a");
+    index.remove(b"b");
+    value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(0, value_log.segment_count());
+
+    Ok(())
+}
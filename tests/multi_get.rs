@@ -0,0 +1,85 @@
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueHandle, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[test]
+fn multi_get() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<NoCompressor>::default().segment_size_bytes(10_000),
+    )?;
+
+    let items = (b'a'..=b'z')
+        .map(|x| (x as char).to_string())
+        .collect::<Vec<_>>();
+
+    {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in &items {
+            let value = key.repeat(1_000);
+            let value = value.as_bytes();
+
+            let key = key.as_bytes();
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key, vhandle, value.len() as u32)?;
+
+            writer.write(key, value)?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    assert!(value_log.segment_count() > 1);
+
+    // Shuffle the handles across segments, with a duplicate,
+    // some cached blobs and a handle into a non-existing segment
+    let mut keys = items.iter().rev().step_by(2).collect::<Vec<_>>();
+    keys.extend(items.iter().step_by(3));
+
+    let mut handles = keys
+        .iter()
+        .map(|key| index.get(key.as_bytes()).map(Option::unwrap))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    value_log.get(&handles[3])?;
+    value_log.get(&handles[10])?;
+
+    handles.push(ValueHandle {
+        segment_id: 999,
+        offset: 0,
+    });
+
+    let results = value_log.multi_get(&handles);
+    assert_eq!(handles.len(), results.len());
+
+    for (key, result) in keys.iter().zip(&results) {
+        let value = result.as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(&**value, key.repeat(1_000).as_bytes());
+    }
+
+    assert!(results.last().unwrap().as_ref().unwrap().is_none());
+
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexWriter, MockIndex, MockIndexWriter, RolloverBudget, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn write_segments(
+    value_log: &ValueLog<NoCompressor>,
+    index: &MockIndex,
+    count: usize,
+) -> value_log::Result<()> {
+    for _ in 0..count {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in ["a", "b", "c"] {
+            let value = key.repeat(1_000);
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key.as_bytes(), value.as_bytes())?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rollover_bounded_max_segments() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+    write_segments(&value_log, &index, 5)?;
+
+    let budget = RolloverBudget::default().max_segments(2);
+
+    let mut ids = value_log.manifest.list_segment_ids();
+    let mut calls = 0;
+
+    loop {
+        let segment_count = value_log.segment_count();
+
+        let progress =
+            value_log.rollover_bounded(&ids, &budget, &index, || MockIndexWriter(index.clone()))?;
+        calls += 1;
+
+        assert!(progress.rewritten_segments.len() <= 2);
+
+        // NOTE: Every rewritten segment is replaced by at most one new segment
+        assert!(value_log.segment_count() <= segment_count + progress.rewritten_segments.len());
+
+        // NOTE: Every call registers its output, so the index is always consistent
+        for (vhandle, _) in index.read().unwrap().values() {
+            assert!(value_log.get(vhandle)?.is_some());
+        }
+
+        if progress.is_done() {
+            break;
+        }
+        ids = progress.remaining_segments;
+    }
+    assert_eq!(3, calls);
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(1, value_log.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn rollover_bounded_max_bytes() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+    write_segments(&value_log, &index, 4)?;
+
+    let segment_size = value_log.manifest.disk_space_used() / 4;
+    let ids = value_log.manifest.list_segment_ids();
+
+    // NOTE: At least one segment is rewritten, even if the budget is too small
+    let budget = RolloverBudget::default().max_bytes(1);
+    let progress =
+        value_log.rollover_bounded(&ids, &budget, &index, || MockIndexWriter(index.clone()))?;
+    assert_eq!(1, progress.rewritten_segments.len());
+    assert_eq!(3, progress.remaining_segments.len());
+
+    let budget = RolloverBudget::default().max_bytes(2 * segment_size);
+    let progress =
+        value_log.rollover_bounded(&progress.remaining_segments, &budget, &index, || {
+            MockIndexWriter(index.clone())
+        })?;
+    assert_eq!(2, progress.rewritten_segments.len());
+    assert_eq!(2 * segment_size, progress.bytes_read);
+    assert_eq!(1, progress.remaining_segments.len());
+
+    assert_eq!(
+        progress.bytes_read - progress.bytes_written,
+        progress.bytes_freed,
+    );
+    assert!(progress.bytes_freed > 0);
+
+    Ok(())
+}
+
+#[test]
+fn rollover_bounded_cancel() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+    write_segments(&value_log, &index, 3)?;
+
+    let ids = value_log.manifest.list_segment_ids();
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let budget = RolloverBudget::default().cancel_token(cancel.clone());
+
+    // NOTE: Nothing is selected after cancelling
+    cancel.store(true, Ordering::Release);
+
+    let progress =
+        value_log.rollover_bounded(&ids, &budget, &index, || MockIndexWriter(index.clone()))?;
+    assert!(progress.rewritten_segments.is_empty());
+    assert_eq!(3, progress.remaining_segments.len());
+    assert!(!progress.is_done());
+
+    cancel.store(false, Ordering::Release);
+
+    // NOTE: Resume where the last call stopped
+    let progress =
+        value_log.rollover_bounded(&progress.remaining_segments, &budget, &index, || {
+            MockIndexWriter(index.clone())
+        })?;
+    assert_eq!(3, progress.rewritten_segments.len());
+    assert!(progress.is_done());
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(1, value_log.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn rollover_bounded_cancel_between_segments() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+    write_segments(&value_log, &index, 3)?;
+
+    let ids = value_log.manifest.list_segment_ids();
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let budget = RolloverBudget::default().cancel_token(cancel.clone());
+
+    // NOTE: Cancel while the first segment is rewritten
+    let progress = value_log.rollover_bounded(&ids, &budget, &index, || {
+        cancel.store(true, Ordering::Release);
+        MockIndexWriter(index.clone())
+    })?;
+    assert_eq!(&ids[..1], &progress.rewritten_segments);
+    assert_eq!(&ids[1..], &progress.remaining_segments);
+
+    // NOTE: The rewritten segment is registered, so the index is consistent
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(2, value_log.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn rollover_bounded_missing_segment() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+    write_segments(&value_log, &index, 2)?;
+
+    let mut ids = value_log.manifest.list_segment_ids();
+    let segment_size = value_log.manifest.disk_space_used() / 2;
+
+    // NOTE: The missing segment does not prevent the others from being rewritten
+    ids.insert(1, 9_999);
+
+    let progress = value_log.rollover_bounded(&ids, &RolloverBudget::default(), &index, || {
+        MockIndexWriter(index.clone())
+    })?;
+    assert!(progress.is_done());
+    assert_eq!(2, progress.rewritten_segments.len());
+    assert!(!progress.rewritten_segments.contains(&9_999));
+    assert_eq!(2 * segment_size, progress.bytes_read);
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(1, value_log.segment_count());
+
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rollover_bounded_drops_dead_blobs() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(folder.path(), Config::<NoCompressor>::default())?;
+
+    // NOTE: Every segment has a live blob, and a blob that is overwritten by the next segment
+    for idx in 0..3 {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in [format!("live{idx}"), "dead".into()] {
+            let value = key.repeat(1_000);
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key.as_bytes(), value.as_bytes())?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    let ids = value_log.manifest.list_segment_ids();
+
+    let progress = value_log.rollover_bounded(&ids, &RolloverBudget::default(), &index, || {
+        MockIndexWriter(index.clone())
+    })?;
+    assert!(progress.is_done());
+    assert!(progress.bytes_written > 0);
+    assert_eq!(
+        progress.bytes_read - progress.bytes_written,
+        progress.bytes_freed,
+    );
+
+    // NOTE: Only the live blobs are rewritten
+    value_log.drop_stale_segments()?;
+    assert_eq!(
+        4,
+        value_log
+            .manifest
+            .list_segments()
+            .iter()
+            .map(|x| x.meta.item_count)
+            .sum::<u64>(),
+    );
+
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockBatchIndexWriter, MockIndex, MockIndexWriter,
+    ValueHandle, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+/// Index reader that overwrites a key right after GC has read it
+struct OverwritingIndexReader {
+    index: MockIndex,
+    key: &'static [u8],
+    new_vhandle: ValueHandle,
+}
+
+impl IndexReader for OverwritingIndexReader {
+    fn get(&self, key: &[u8]) -> std::io::Result<Option<ValueHandle>> {
+        let vhandle = self.index.get(key)?;
+
+        if key == self.key {
+            MockIndexWriter(self.index.clone()).insert_indirect(
+                key,
+                self.new_vhandle.clone(),
+                1,
+            )?;
+        }
+
+        Ok(vhandle)
+    }
+}
+
+#[test]
+fn rollover_cas_overwrite_between_read_and_write() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let mut new_b = None;
+
+    for value in ["old", "new"] {
+        let mut writer = value_log.get_writer()?;
+
+        for key in ["a", "b", "c"] {
+            let vhandle = writer.get_next_value_handle();
+
+            // NOTE: The new version of "b" is not in the index yet
+            if value == "new" && key == "b" {
+                new_b = Some(vhandle);
+            } else if value == "old" || key == "a" {
+                MockIndexWriter(index.clone()).insert_indirect(key.as_bytes(), vhandle, 3)?;
+            }
+
+            writer.write(key, value)?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    let new_b = new_b.unwrap();
+
+    let reader = OverwritingIndexReader {
+        index: index.clone(),
+        key: b"b",
+        new_vhandle: new_b.clone(),
+    };
+
+    value_log.rollover(&[0], &reader, MockBatchIndexWriter::new(index.clone()))?;
+
+    // NOTE: "a" was overwritten before GC, so it was not rewritten
+    let vhandle = index.get(b"a")?.unwrap();
+    assert_eq!(1, vhandle.segment_id);
+    assert_eq!(b"new", &*value_log.get(&vhandle)?.unwrap());
+
+    // NOTE: "b" was overwritten during GC, so the relocation was dropped
+    let vhandle = index.get(b"b")?.unwrap();
+    assert_eq!(new_b, vhandle);
+    assert_eq!(b"new", &*value_log.get(&vhandle)?.unwrap());
+
+    // NOTE: "c" was relocated
+    let vhandle = index.get(b"c")?.unwrap();
+    assert_eq!(2, vhandle.segment_id);
+    assert_eq!(b"old", &*value_log.get(&vhandle)?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn rollover_cas_concurrent_writes() -> value_log::Result<()> {
+    const KEY_COUNT: usize = 20;
+
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let write_version = |version: usize| -> value_log::Result<()> {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in 0..KEY_COUNT {
+            let key = key.to_string();
+            let value = format!("{key}-{version}");
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(&key, &value)?;
+        }
+
+        value_log.register_writer(writer)
+    };
+
+    write_version(0)?;
+
+    let done = Arc::new(AtomicBool::default());
+    let last_version = 200;
+
+    std::thread::scope(|scope| -> value_log::Result<()> {
+        let writer_thread = scope.spawn({
+            let done = done.clone();
+
+            move || -> value_log::Result<()> {
+                for version in 1..=last_version {
+                    write_version(version)?;
+                }
+
+                done.store(true, Ordering::Release);
+                Ok(())
+            }
+        });
+
+        while !done.load(Ordering::Acquire) {
+            let ids = value_log.manifest.list_segment_ids();
+            value_log.rollover(&ids, &index, MockBatchIndexWriter::new(index.clone()))?;
+            value_log.drop_stale_segments()?;
+        }
+
+        writer_thread.join().expect("should join")
+    })?;
+
+    for key in 0..KEY_COUNT {
+        let vhandle = index.get(key.to_string().as_bytes())?.unwrap();
+        let value = value_log.get(&vhandle)?.unwrap();
+        assert_eq!(format!("{key}-{last_version}").as_bytes(), &*value);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rollover_cas_duplicate_key_in_segment() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+    let mut index_writer = MockIndexWriter(index.clone());
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let mut writer = value_log.get_writer()?;
+
+    for value in ["old", "new"] {
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(b"a", vhandle, 3)?;
+
+        writer.write("a", value)?;
+    }
+
+    value_log.register_writer(writer)?;
+
+    value_log.rollover(&[0], &index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+
+    // NOTE: Only the blob the index points to is rewritten
+    let vhandle = index.get(b"a")?.unwrap();
+    assert_eq!(1, vhandle.segment_id);
+    assert_eq!(b"new", &*value_log.get(&vhandle)?.unwrap());
+
+    let segment = value_log.manifest.get_segment(1).unwrap();
+    assert_eq!(1, segment.meta.item_count);
+
+    Ok(())
+}
+
+#[test]
+fn rollover_cas_mock_compares_on_finish() -> value_log::Result<()> {
+    let index = MockIndex::default();
+
+    let old = ValueHandle {
+        segment_id: 0,
+        offset: 0,
+    };
+    let relocated = ValueHandle {
+        segment_id: 1,
+        offset: 0,
+    };
+    let new = ValueHandle {
+        segment_id: 2,
+        offset: 0,
+    };
+
+    MockIndexWriter(index.clone()).insert_indirect(b"a", old.clone(), 3)?;
+
+    let mut gc_writer = MockBatchIndexWriter::new(index.clone());
+    gc_writer.insert_indirect_if(b"a", &old, relocated, 3)?;
+
+    // NOTE: The key is overwritten after the relocation is queued, but before it is applied
+    MockIndexWriter(index.clone()).insert_indirect(b"a", new.clone(), 3)?;
+
+    gc_writer.finish()?;
+
+    assert_eq!(Some(new), index.get(b"a")?);
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::sync::Mutex;
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueHandle, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+/// Index reader that records the batches it was asked for
+#[derive(Default)]
+struct BatchingIndexReader {
+    index: MockIndex,
+    batches: Mutex<Vec<Vec<Vec<u8>>>>,
+    truncate: bool,
+}
+
+impl IndexReader for BatchingIndexReader {
+    fn get(&self, _: &[u8]) -> std::io::Result<Option<ValueHandle>> {
+        panic!("should use get_many");
+    }
+
+    fn get_many(&self, keys: &[&[u8]]) -> std::io::Result<Vec<Option<ValueHandle>>> {
+        self.batches
+            .lock()
+            .unwrap()
+            .push(keys.iter().map(|x| x.to_vec()).collect());
+
+        let mut vhandles = self.index.get_many(keys)?;
+
+        if self.truncate {
+            vhandles.pop();
+        }
+
+        Ok(vhandles)
+    }
+}
+
+fn write_items(value_log: &ValueLog<NoCompressor>, index: &MockIndex) -> value_log::Result<()> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    for key in 0..1_000u32 {
+        let key = key.to_be_bytes();
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(&key, vhandle, 3)?;
+
+        writer.write(key, "abc")?;
+    }
+
+    value_log.register_writer(writer)
+}
+
+#[test]
+fn rollover_get_many() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let reader = BatchingIndexReader::default();
+    write_items(&value_log, &reader.index)?;
+
+    // NOTE: Removed keys are not rewritten
+    reader.index.remove(&0u32.to_be_bytes());
+
+    value_log.rollover(&[0], &reader, MockIndexWriter(reader.index.clone()))?;
+
+    let batches = reader.batches.into_inner().unwrap();
+    assert!(batches.len() > 1);
+    assert!(batches.len() < 1_000);
+
+    let keys = batches.into_iter().flatten().collect::<Vec<_>>();
+    assert_eq!(1_000, keys.len());
+    assert!(keys.windows(2).all(|x| x[0] < x[1]));
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(1, value_log.segment_count());
+    assert_eq!(999, value_log.manifest.list_segments()[0].meta.item_count);
+
+    for key in 1..1_000u32 {
+        let vhandle = reader.index.get(&key.to_be_bytes())?.unwrap();
+        assert_eq!(b"abc", &*value_log.get(&vhandle)?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rollover_get_many_wrong_length() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let reader = BatchingIndexReader {
+        truncate: true,
+        ..Default::default()
+    };
+    write_items(&value_log, &reader.index)?;
+
+    assert!(value_log
+        .rollover(&[0], &reader, MockIndexWriter(reader.index.clone()))
+        .is_err());
+
+    // NOTE: The old segment is still alive
+    value_log.drop_stale_segments()?;
+    assert_eq!(0, value_log.manifest.list_segments()[0].id);
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, Slice, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+#[test]
+fn rollover_partitioned() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    // NOTE: Both segments span the entire key space
+    let keys = ('a'..='z').map(|x| x.to_string()).collect::<Vec<_>>();
+
+    for chunk in [0, 1] {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in keys.iter().skip(chunk).step_by(2) {
+            let value = key.repeat(1_000);
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key, &value)?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    let ids = value_log.manifest.list_segment_ids();
+    assert_eq!(2, ids.len());
+
+    // NOTE: Boundaries before or after all keys do not create empty segments
+    let boundaries: Vec<Slice> = vec!["0".into(), "p".into(), "g".into(), "zz".into()];
+
+    value_log.rollover_partitioned(&ids, &boundaries, &index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+
+    let mut key_ranges = value_log
+        .manifest
+        .list_segments()
+        .into_iter()
+        .map(|x| x.meta.key_range.clone())
+        .collect::<Vec<_>>();
+    key_ranges.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let key_ranges = key_ranges
+        .iter()
+        .map(|x| (&*x.0, &*x.1))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        vec![
+            (b"a".as_slice(), b"f".as_slice()),
+            (b"g", b"o"),
+            (b"p", b"z"),
+        ],
+        key_ranges,
+    );
+
+    for key in &keys {
+        let vhandle = index.get(key.as_bytes())?.unwrap();
+        let value = value_log.get(&vhandle)?.unwrap();
+        assert_eq!(key.repeat(1_000).as_bytes(), &*value);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rollover_partitioned_respects_segment_size() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<NoCompressor>::default().segment_size_bytes(2_500),
+    )?;
+
+    {
+        let mut index_writer = MockIndexWriter(index.clone());
+        let mut writer = value_log.get_writer()?;
+
+        for key in ["a", "b", "c", "d", "e", "f"] {
+            let value = key.repeat(1_000);
+
+            let vhandle = writer.get_next_value_handle();
+            index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+            writer.write(key, &value)?;
+        }
+
+        value_log.register_writer(writer)?;
+    }
+
+    let ids = value_log.manifest.list_segment_ids();
+
+    value_log.rollover_partitioned(&ids, &["f".into()], &index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+
+    // NOTE: [a, c], [d, e] by size, [f] by boundary
+    assert_eq!(3, value_log.segment_count());
+
+    for segment in value_log.manifest.list_segments() {
+        let (min, max) = &*segment.meta.key_range;
+        assert!(max < &Slice::from("f") || min >= &Slice::from("f"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+// A stats scan must not block writers, so flushes can proceed while
+// a (potentially very long) index scan is running.
+
+use test_log::test;
+use value_log::{
+    Compressor, Config, IndexWriter, MockIndex, MockIndexWriter, SpaceAmpStrategy, ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn write_blob(
+    value_log: &ValueLog<NoCompressor>,
+    index: &MockIndex,
+    key: &[u8],
+    value: &[u8],
+) -> value_log::Result<()> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    let vhandle = writer.get_next_value_handle();
+    index_writer.insert_indirect(key, vhandle, value.len() as u32)?;
+
+    writer.write(key, value)?;
+    value_log.register_writer(writer)?;
+
+    Ok(())
+}
+
+#[test]
+fn scan_non_blocking_register_writer() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    for key in ["a", "b"] {
+        write_blob(&value_log, &index, key.as_bytes(), key.as_bytes())?;
+    }
+    assert_eq!(2, value_log.segment_count());
+
+    let snapshot = index.read().unwrap().values().cloned().collect::<Vec<_>>();
+
+    // NOTE: Flush new blob files in the middle of the scan
+    let report =
+        value_log.scan_for_stats(snapshot.into_iter().enumerate().map(|(idx, item)| {
+            if idx == 0 {
+                write_blob(&value_log, &index, b"c", b"c").map_err(std::io::Error::other)?;
+                value_log
+                    .drop_stale_segments()
+                    .map_err(std::io::Error::other)?;
+            }
+            Ok(item)
+        }))?;
+
+    // NOTE: The new blob file is not part of the report, and is not marked as stale
+    assert_eq!(0, report.stale_segment_count);
+    assert_eq!(2, report.total_blobs);
+
+    value_log.drop_stale_segments()?;
+    assert_eq!(3, value_log.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn scan_non_blocking_rollover() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+
+    let key = b"key";
+    let value = "value".repeat(1_000);
+
+    for _ in 0..4 {
+        write_blob(&value_log, &index, key, value.as_bytes())?;
+    }
+
+    value_log.scan_for_stats(index.read().unwrap().values().cloned().map(Ok))?;
+    assert_eq!(4.0, value_log.space_amp());
+
+    let snapshot = index.read().unwrap().values().cloned().collect::<Vec<_>>();
+
+    // NOTE: Garbage collect in the middle of the scan
+    let report =
+        value_log.scan_for_stats(snapshot.into_iter().enumerate().map(|(idx, item)| {
+            if idx == 0 {
+                let strategy = SpaceAmpStrategy::new(1.0);
+                value_log
+                    .apply_gc_strategy(&strategy, &index, MockIndexWriter(index.clone()))
+                    .map_err(std::io::Error::other)?;
+                value_log
+                    .drop_stale_segments()
+                    .map_err(std::io::Error::other)?;
+            }
+            Ok(item)
+        }))?;
+
+    // NOTE: The dropped segments are skipped
+    assert_eq!(1, report.total_blobs);
+    assert_eq!(0, report.stale_blobs);
+
+    assert_eq!(1, value_log.segment_count());
+    assert_eq!(1.0, value_log.space_amp());
+
+    for (vhandle, _) in index.read().unwrap().values() {
+        assert!(value_log.get(vhandle)?.is_some());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use test_log::test;
+use value_log::{
+    CompressionType, Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter,
+    ValueLog,
+};
+
+/// "Compresses" by XORing every byte with a key
+#[derive(Clone, Debug)]
+struct XorCompressor(u8);
+
+impl Default for XorCompressor {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl Compressor for XorCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.iter().map(|x| x ^ self.0).collect())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        self.compress(bytes)
+    }
+
+    fn compression_type(&self) -> CompressionType {
+        CompressionType {
+            id: 1,
+            params: vec![self.0],
+        }
+    }
+
+    fn decompressor_for(&self, compression_type: &CompressionType) -> Option<Self> {
+        match (compression_type.id, compression_type.params.as_slice()) {
+            (1, [key]) => Some(Self(*key)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+fn write_items<C: Compressor + Clone>(
+    value_log: &ValueLog<C>,
+    index: &MockIndex,
+    items: &[&str],
+) -> value_log::Result<()> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    for key in items {
+        let value = key.repeat(1_000);
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key.as_bytes(), vhandle, value.len() as u32)?;
+
+        writer.write(key, &value)?;
+    }
+
+    value_log.register_writer(writer)
+}
+
+fn check_items<C: Compressor + Clone>(
+    value_log: &ValueLog<C>,
+    index: &MockIndex,
+    items: &[&str],
+) -> value_log::Result<()> {
+    for key in items {
+        let vhandle = index.get(key.as_bytes())?.unwrap();
+        let value = value_log.get(&vhandle)?.unwrap();
+        assert_eq!(&*value, key.repeat(1_000).as_bytes());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn segment_compression_type_mixed() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::default().compression(XorCompressor(1)))?;
+        write_items(&value_log, &index, &["a", "b"])?;
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::default().compression(XorCompressor(2)))?;
+        write_items(&value_log, &index, &["c", "d"])?;
+
+        check_items(&value_log, &index, &["a", "b", "c", "d"])?;
+
+        // NOTE: Rewrites "a" and "b" with the new compression
+        value_log.major_compact(&index, MockIndexWriter(index.clone()))?;
+        value_log.drop_stale_segments()?;
+
+        check_items(&value_log, &index, &["a", "b", "c", "d"])?;
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::default().compression(XorCompressor(3)))?;
+
+        for segment in value_log.manifest.list_segments() {
+            assert_eq!(
+                Some(XorCompressor(2).compression_type()),
+                segment.meta.compression,
+            );
+        }
+
+        check_items(&value_log, &index, &["a", "b", "c", "d"])?;
+    }
+
+    assert!(matches!(
+        ValueLog::open(vl_path, Config::<NoCompressor>::default()),
+        Err(value_log::Error::UnsupportedCompression(CompressionType {
+            id: 1,
+            ..
+        })),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn segment_compression_type_v1_vlog() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    // NOTE: Copy fixture, so we can write into it
+    let fixture_path = std::path::Path::new("test_fixture/v1_vlog");
+    std::fs::create_dir_all(vl_path.join("segments"))?;
+    std::fs::copy(fixture_path.join(".vlog"), vl_path.join(".vlog"))?;
+    std::fs::copy(
+        fixture_path.join("vlog_manifest"),
+        vl_path.join("vlog_manifest"),
+    )?;
+    for dirent in std::fs::read_dir(fixture_path.join("segments"))? {
+        let dirent = dirent?;
+        std::fs::copy(
+            dirent.path(),
+            vl_path.join("segments").join(dirent.file_name()),
+        )?;
+    }
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<NoCompressor>::default())?;
+    assert_eq!(2, value_log.segment_count());
+
+    write_items(&value_log, &index, &["x", "y"])?;
+    assert_eq!(3, value_log.segment_count());
+
+    let count = value_log.get_reader()?.map(|kv| kv.map(|_| ())).count();
+    assert_eq!(6, count);
+    assert_eq!(0, value_log.verify()?);
+
+    check_items(&value_log, &index, &["x", "y"])?;
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use std::io::Read;
+use test_log::test;
+use value_log::{
+    ChecksumVerification, Compressor, Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter,
+    ValueLog,
+};
+
+#[derive(Clone, Default)]
+struct NoCompressor;
+
+impl Compressor for NoCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.into())
+    }
+}
+
+/// Compressor that halves repeated bytes, so chunks are stored compressed
+#[derive(Clone, Default)]
+struct HalvingCompressor;
+
+impl Compressor for HalvingCompressor {
+    fn compress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.iter().step_by(2).copied().collect())
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> value_log::Result<Vec<u8>> {
+        Ok(bytes.iter().flat_map(|&x| [x, x]).collect())
+    }
+}
+
+fn large_value(len: usize) -> Vec<u8> {
+    (0..len).map(|x| ((x / 2) % 251) as u8).collect()
+}
+
+/// Reader that fails after returning the given amount of bytes
+struct FailingReader<'a> {
+    data: &'a [u8],
+    fail_after: usize,
+}
+
+impl Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.fail_after == 0 {
+            return Err(std::io::Error::other("upload aborted"));
+        }
+
+        let n = buf.len().min(self.data.len()).min(self.fail_after);
+        buf[..n].copy_from_slice(&self.data[..n]);
+
+        self.data = &self.data[n..];
+        self.fail_after -= n;
+
+        Ok(n)
+    }
+}
+
+fn read_blob<C: Compressor + Clone>(
+    value_log: &ValueLog<C>,
+    index: &MockIndex,
+    key: &[u8],
+) -> value_log::Result<Vec<u8>> {
+    let vhandle = index.get(key)?.unwrap();
+
+    let mut buf = vec![];
+    value_log
+        .open_blob(&vhandle)?
+        .unwrap()
+        .read_to_end(&mut buf)?;
+
+    assert_eq!(&buf, &*value_log.get(&vhandle)?.unwrap());
+
+    Ok(buf)
+}
+
+#[test]
+fn write_stream_uncompressed() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+    let mut index_writer = MockIndexWriter(index.clone());
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<NoCompressor>::default().large_blob_threshold(100_000),
+    )?;
+
+    let large = large_value(300_000);
+
+    let mut writer = value_log.get_writer()?;
+
+    for (key, value) in [(b"large", &*large), (b"small", b"abc")] {
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(key, vhandle, value.len() as u32)?;
+
+        writer.write_stream(key, value, value.len() as u32)?;
+    }
+
+    value_log.register_writer(writer)?;
+
+    assert_eq!(large, read_blob(&value_log, &index, b"large")?);
+    assert_eq!(b"abc", &*read_blob(&value_log, &index, b"small")?);
+    assert_eq!(0, value_log.verify()?);
+
+    let segment = value_log.manifest.list_segments().pop().unwrap();
+    assert_eq!(2, segment.meta.item_count);
+    assert_eq!(300_003, segment.meta.total_uncompressed_bytes);
+
+    Ok(())
+}
+
+#[test]
+fn write_stream_chunked() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    // NOTE: Always go to disk, so every read is checked against the stored checksum
+    let config = || {
+        Config::<HalvingCompressor>::default()
+            .large_blob_threshold(100_000)
+            .checksum_verification(ChecksumVerification::Always)
+    };
+
+    let large = large_value(300_002);
+
+    {
+        let mut index_writer = MockIndexWriter(index.clone());
+
+        let value_log = ValueLog::open(vl_path, config())?;
+        let mut writer = value_log.get_writer()?;
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(b"large", vhandle, large.len() as u32)?;
+
+        let bytes_written = writer.write_stream(b"large", &*large, large.len() as u32)?;
+        assert!(bytes_written < large.len() as u32);
+
+        value_log.register_writer(writer)?;
+    }
+
+    let value_log = ValueLog::open(vl_path, config())?;
+
+    let vhandle = index.get(b"large")?.unwrap();
+    assert!(value_log.open_blob(&vhandle)?.unwrap().is_streaming());
+    assert_eq!(large, read_blob(&value_log, &index, b"large")?);
+    assert_eq!(0, value_log.verify()?);
+
+    // NOTE: Streamed blobs can be rewritten like any other blob
+    value_log.major_compact(&index, MockIndexWriter(index.clone()))?;
+    value_log.drop_stale_segments()?;
+
+    assert_eq!(large, read_blob(&value_log, &index, b"large")?);
+    assert_eq!(0, value_log.verify()?);
+
+    Ok(())
+}
+
+#[test]
+fn write_stream_reader_fails() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+    let mut index_writer = MockIndexWriter(index.clone());
+
+    let value_log = ValueLog::open(
+        vl_path,
+        Config::<HalvingCompressor>::default().large_blob_threshold(100_000),
+    )?;
+
+    let large = large_value(300_000);
+
+    let mut writer = value_log.get_writer()?;
+    let offset = writer.offset();
+
+    let result = writer.write_stream(
+        b"aborted",
+        FailingReader {
+            data: &large,
+            fail_after: 150_000,
+        },
+        large.len() as u32,
+    );
+    assert!(matches!(result, Err(value_log::Error::Io(_))));
+
+    // NOTE: Reader ends too early
+    let result = writer.write_stream(b"short", &large[..1_000], large.len() as u32);
+    assert!(matches!(
+        result,
+        Err(value_log::Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof,
+    ));
+
+    // NOTE: The partially written blobs are discarded
+    assert_eq!(offset, writer.offset());
+
+    let vhandle = writer.get_next_value_handle();
+    index_writer.insert_indirect(b"large", vhandle, large.len() as u32)?;
+    writer.write_stream(b"large", &*large, large.len() as u32)?;
+
+    value_log.register_writer(writer)?;
+
+    assert_eq!(large, read_blob(&value_log, &index, b"large")?);
+
+    let segment = value_log.manifest.list_segments().pop().unwrap();
+    assert_eq!(1, segment.meta.item_count);
+
+    Ok(())
+}
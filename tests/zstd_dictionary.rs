@@ -0,0 +1,145 @@
+#![cfg(feature = "zstd")]
+
+use test_log::test;
+use value_log::{
+    Config, IndexReader, IndexWriter, MockIndex, MockIndexWriter, ValueLog, ZstdCompressor,
+};
+
+fn document(idx: usize) -> String {
+    format!(
+        r#"{{"id":{idx},"name":"user-{idx}","email":"user-{idx}@example.com","active":{},"roles":["reader","writer"],"created_at":"2024-01-{:0>2}T00:00:00Z"}}"#,
+        idx % 2 == 0,
+        idx % 28 + 1,
+    )
+}
+
+fn write_documents(
+    value_log: &ValueLog<ZstdCompressor>,
+    index: &MockIndex,
+    range: std::ops::Range<usize>,
+) -> value_log::Result<()> {
+    let mut index_writer = MockIndexWriter(index.clone());
+    let mut writer = value_log.get_writer()?;
+
+    for idx in range {
+        let key = idx.to_be_bytes();
+        let value = document(idx);
+
+        let vhandle = writer.get_next_value_handle();
+        index_writer.insert_indirect(&key, vhandle, value.len() as u32)?;
+        writer.write(key, value)?;
+    }
+
+    value_log.register_writer(writer)
+}
+
+fn check_documents(
+    value_log: &ValueLog<ZstdCompressor>,
+    index: &MockIndex,
+    range: std::ops::Range<usize>,
+) -> value_log::Result<()> {
+    for idx in range {
+        let vhandle = index.get(&idx.to_be_bytes())?.unwrap();
+        let value = value_log.get(&vhandle)?.unwrap();
+        assert_eq!(&*value, document(idx).as_bytes());
+    }
+
+    Ok(())
+}
+
+fn dictionary_ids(value_log: &ValueLog<ZstdCompressor>) -> Vec<Option<u32>> {
+    let mut segments = value_log.manifest.list_segments();
+    segments.sort_by_key(|x| x.id);
+
+    segments
+        .iter()
+        .map(|x| {
+            let params = &x.meta.compression.as_ref().unwrap().params;
+            params
+                .get(4..8)
+                .map(|id| u32::from_be_bytes(id.try_into().unwrap()))
+        })
+        .collect()
+}
+
+#[test]
+fn zstd_dictionary_train() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    let value_log = ValueLog::open(vl_path, Config::<ZstdCompressor>::default())?;
+
+    write_documents(&value_log, &index, 0..2_000)?;
+
+    let id = value_log.train_zstd_dictionary(1_000, 4_096)?;
+    assert_eq!(1, id);
+    assert!(vl_path.join("dictionaries").join("1").try_exists()?);
+
+    write_documents(&value_log, &index, 2_000..4_000)?;
+    assert_eq!(vec![None, Some(1)], dictionary_ids(&value_log));
+
+    {
+        let segments = value_log.manifest.list_segments();
+        let mut sizes = segments
+            .iter()
+            .map(|x| (x.id, x.meta.compressed_bytes))
+            .collect::<Vec<_>>();
+        sizes.sort_unstable();
+
+        // NOTE: Small documents compress much better with a dictionary
+        assert!(sizes[1].1 < sizes[0].1);
+    }
+
+    check_documents(&value_log, &index, 0..4_000)?;
+    assert_eq!(0, value_log.verify()?);
+
+    Ok(())
+}
+
+#[test]
+fn zstd_dictionary_recover_and_rollover() -> value_log::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let vl_path = folder.path();
+
+    let index = MockIndex::default();
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<ZstdCompressor>::default())?;
+        write_documents(&value_log, &index, 0..1_000)?;
+        value_log.train_zstd_dictionary(1_000, 4_096)?;
+        write_documents(&value_log, &index, 1_000..2_000)?;
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<ZstdCompressor>::default())?;
+        assert_eq!(vec![None, Some(1)], dictionary_ids(&value_log));
+        check_documents(&value_log, &index, 0..2_000)?;
+
+        let id = value_log.train_zstd_dictionary(1_000, 4_096)?;
+        assert_eq!(2, id);
+
+        // NOTE: Rollover re-encodes all blobs with the newest dictionary
+        value_log.major_compact(&index, MockIndexWriter(index.clone()))?;
+        value_log.drop_stale_segments()?;
+
+        assert_eq!(vec![Some(2)], dictionary_ids(&value_log));
+        check_documents(&value_log, &index, 0..2_000)?;
+    }
+
+    {
+        let value_log = ValueLog::open(vl_path, Config::<ZstdCompressor>::default())?;
+        assert_eq!(vec![Some(2)], dictionary_ids(&value_log));
+        check_documents(&value_log, &index, 0..2_000)?;
+    }
+
+    std::fs::remove_file(vl_path.join("dictionaries").join("2"))?;
+
+    assert!(matches!(
+        ValueLog::open(vl_path, Config::<ZstdCompressor>::default()),
+        Err(value_log::Error::UnsupportedCompression(_)),
+    ));
+
+    Ok(())
+}